@@ -0,0 +1,368 @@
+// HTTP-вызовы к AI API: общий клиент с таймаутами, классификация ошибок
+// и повторы с экспоненциальной задержкой (с учётом Retry-After).
+use std::time::{Duration, SystemTime};
+
+use reqwest::header::HeaderMap;
+use reqwest::{RequestBuilder, StatusCode};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::config::Config;
+use crate::logging::{compact, log, Level};
+
+/// Верхняя граница одной паузы между попытками; если Retry-After просит
+/// ждать дольше, запрос не повторяется, а ошибка возвращается вызывающему.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Верхняя граница паузы, которую может запросить сервер в Retry-After.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(3600);
+
+/// Ошибка обращения к AI API.
+#[derive(Debug, Error)]
+pub enum GenError {
+    #[error("ошибка авторизации ({status}): {message}")]
+    Auth { status: u16, message: String },
+    #[error("исчерпана квота: {message}")]
+    Quota { message: String },
+    #[error("превышен лимит запросов: {message}")]
+    RateLimit {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    #[error("ответ отклонён фильтром контента: {message}")]
+    ContentFilter { message: String },
+    #[error("ошибка сервера ({status}): {message}")]
+    Server {
+        status: u16,
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    #[error("ошибка соединения: {0}")]
+    Transport(#[from] reqwest::Error),
+    #[error("некорректный ответ ({status}): {message}")]
+    BadResponse { status: u16, message: String },
+}
+
+impl GenError {
+    /// Имеет ли смысл повторить запрос.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            GenError::RateLimit { .. } | GenError::Server { .. } | GenError::Transport(_)
+        )
+    }
+
+    /// Пауза, запрошенная сервером через Retry-After.
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            GenError::RateLimit { retry_after, .. } | GenError::Server { retry_after, .. } => {
+                *retry_after
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Параметры HTTP-вызовов: таймаут и повторы.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            max_retries: cfg.ai_max_retries,
+            base_delay: Duration::from_millis(cfg.ai_retry_base_ms),
+        }
+    }
+
+    /// Экспоненциальная задержка перед попыткой `attempt` (начиная с 1).
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(MAX_BACKOFF)
+    }
+}
+
+/// Клиент с таймаутом на запрос из `ai_timeout_secs`.
+pub fn build_client(cfg: &Config) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(cfg.ai_timeout_secs))
+        .connect_timeout(Duration::from_secs(cfg.ai_timeout_secs.min(10)))
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new())
+}
+
+/// Отправляет JSON-запрос, повторяя его при временных ошибках.
+/// `build` вызывается на каждую попытку, чтобы собрать запрос заново.
+/// Пауза из Retry-After не сокращается: если она длиннее `MAX_BACKOFF`,
+/// возвращается ошибка (пул провайдеров ставит запись на паузу на это время).
+pub async fn send_json(
+    ssys: &str,
+    policy: RetryPolicy,
+    build: impl Fn() -> RequestBuilder,
+) -> Result<Value, GenError> {
+    let mut attempt = 0;
+    loop {
+        let err = match send_once(build()).await {
+            Ok(val) => return Ok(val),
+            Err(err) => err,
+        };
+        attempt += 1;
+        if !err.is_retryable() || attempt > policy.max_retries {
+            return Err(err);
+        }
+        let delay = match err.retry_after() {
+            Some(wait) if wait > MAX_BACKOFF => {
+                log(
+                    ssys,
+                    "retry",
+                    Level::Warn,
+                    "Сервер просит подождать дольше допустимого, не повторяем",
+                )
+                .data("retry_after_s", wait.as_secs().to_string())
+                .data("error", err.to_string())
+                .print();
+                return Err(err);
+            }
+            Some(wait) => wait,
+            None => policy.backoff(attempt),
+        };
+        log(
+            ssys,
+            "retry",
+            Level::Warn,
+            "Временная ошибка, повторяем запрос",
+        )
+        .data("attempt", attempt.to_string())
+        .data("delay_ms", delay.as_millis().to_string())
+        .data("error", err.to_string())
+        .print();
+        tokio::time::sleep(delay).await;
+    }
+}
+
+async fn send_once(req: RequestBuilder) -> Result<Value, GenError> {
+    let resp = req.send().await?;
+    let status = resp.status();
+    let headers = resp.headers().clone();
+    let text = resp.text().await?;
+    let val: Value = serde_json::from_str(&text).unwrap_or(Value::String(text));
+    if status.is_success() {
+        if val.is_string() {
+            return Err(GenError::BadResponse {
+                status: status.as_u16(),
+                message: compact(&val.to_string(), 200),
+            });
+        }
+        return Ok(val);
+    }
+    Err(classify(status, &headers, &val))
+}
+
+/// Раскладывает неуспешный ответ по типам ошибок.
+pub fn classify(status: StatusCode, headers: &HeaderMap, body: &Value) -> GenError {
+    let error = &body["error"];
+    let message = error["message"]
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| compact(&body.to_string(), 200));
+    let code = error["code"].as_str().unwrap_or_default();
+    let kind = error["type"].as_str().unwrap_or_default();
+    let retry_after = parse_retry_after(headers);
+
+    match status.as_u16() {
+        401 | 403 => GenError::Auth {
+            status: status.as_u16(),
+            message,
+        },
+        429 if code == "insufficient_quota" || kind == "insufficient_quota" => {
+            GenError::Quota { message }
+        }
+        429 => GenError::RateLimit {
+            message,
+            retry_after,
+        },
+        400 if is_content_filter(code) || is_content_filter(kind) => {
+            GenError::ContentFilter { message }
+        }
+        408 | 500..=599 => GenError::Server {
+            status: status.as_u16(),
+            message,
+            retry_after,
+        },
+        _ => GenError::BadResponse {
+            status: status.as_u16(),
+            message,
+        },
+    }
+}
+
+/// Коды, которыми OpenAI-совместимые API сообщают о срабатывании фильтра.
+pub fn is_content_filter(code: &str) -> bool {
+    matches!(code, "content_filter" | "content_policy_violation")
+}
+
+/// Читает `retry-after-ms` или `retry-after` (секунды или HTTP-дата).
+/// Некорректные значения игнорируются, слишком большие ограничиваются
+/// `MAX_RETRY_AFTER`.
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+    };
+    let seconds = |value: f64| {
+        (!value.is_nan())
+            .then(|| value.min(MAX_RETRY_AFTER.as_secs_f64()))
+            .and_then(|v| Duration::try_from_secs_f64(v).ok())
+    };
+    if let Some(ms) = header("retry-after-ms").and_then(|v| v.parse::<f64>().ok()) {
+        return seconds(ms / 1000.0);
+    }
+    let value = header(reqwest::header::RETRY_AFTER.as_str())?;
+    match value.parse::<f64>() {
+        Ok(secs) => seconds(secs),
+        Err(_) => httpdate::parse_http_date(value).ok().map(|at| {
+            at.duration_since(SystemTime::now())
+                .unwrap_or_default()
+                .min(MAX_RETRY_AFTER)
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stub_server::{self, StubResponse};
+    use serde_json::json;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut h = HeaderMap::new();
+        for (k, v) in pairs {
+            h.insert(*k, v.parse().unwrap());
+        }
+        h
+    }
+
+    #[test]
+    fn classifies_openai_errors() {
+        let none = HeaderMap::new();
+        let quota = json!({"error": {"message": "pay", "type": "insufficient_quota", "code": "insufficient_quota"}});
+        assert!(matches!(
+            classify(StatusCode::TOO_MANY_REQUESTS, &none, &quota),
+            GenError::Quota { .. }
+        ));
+        let limited = json!({"error": {"message": "slow down", "code": "rate_limit_exceeded"}});
+        match classify(
+            StatusCode::TOO_MANY_REQUESTS,
+            &headers(&[("retry-after", "7")]),
+            &limited,
+        ) {
+            GenError::RateLimit { retry_after, .. } => {
+                assert_eq!(retry_after, Some(Duration::from_secs(7)))
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        let filtered = json!({"error": {"message": "nope", "code": "content_policy_violation"}});
+        assert!(matches!(
+            classify(StatusCode::BAD_REQUEST, &none, &filtered),
+            GenError::ContentFilter { .. }
+        ));
+        assert!(matches!(
+            classify(StatusCode::UNAUTHORIZED, &none, &json!({})),
+            GenError::Auth { .. }
+        ));
+        assert!(classify(StatusCode::BAD_GATEWAY, &none, &json!({})).is_retryable());
+        assert!(!classify(StatusCode::BAD_REQUEST, &none, &json!({})).is_retryable());
+    }
+
+    #[test]
+    fn parses_retry_after_safely() {
+        let parse = |pairs: &[(&'static str, &str)]| parse_retry_after(&headers(pairs));
+        assert_eq!(
+            parse(&[("retry-after-ms", "1500")]),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(parse(&[("retry-after", "inf")]), Some(MAX_RETRY_AFTER));
+        assert_eq!(parse(&[("retry-after", "NaN")]), None);
+        assert_eq!(parse(&[("retry-after", "-3")]), None);
+        assert_eq!(parse(&[("retry-after", "1e30")]), Some(MAX_RETRY_AFTER));
+        let soon = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(120));
+        let delay = parse(&[("retry-after", &soon)]).unwrap();
+        assert!(delay > Duration::from_secs(100) && delay <= Duration::from_secs(120));
+        assert_eq!(
+            parse(&[("retry-after", "Wed, 21 Oct 2015 07:28:00 GMT")]),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse(&[("retry-after", "завтра")]), None);
+    }
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            base_delay: Duration::from_millis(500),
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(3), Duration::from_millis(2000));
+        assert_eq!(policy.backoff(20), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn retries_rate_limit_then_succeeds() {
+        let (base, server) = stub_server::start(vec![
+            StubResponse::json(429, json!({"error": {"message": "later"}}))
+                .header("retry-after", "0"),
+            StubResponse::json(200, json!({"ok": true})),
+        ]);
+        let client = reqwest::Client::new();
+        let policy = RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::from_millis(1),
+        };
+        let val = send_json("test", policy, || client.post(&base).json(&json!({})))
+            .await
+            .unwrap();
+        assert_eq!(val["ok"], true);
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn long_retry_after_is_not_shortened() {
+        let (base, server) = stub_server::start(vec![StubResponse::json(
+            429,
+            json!({"error": {"message": "later"}}),
+        )
+        .header("retry-after", "300")]);
+        let client = reqwest::Client::new();
+        let policy = RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(1),
+        };
+        let err = send_json("test", policy, || client.post(&base).json(&json!({})))
+            .await
+            .unwrap_err();
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(300)));
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn auth_error_is_not_retried() {
+        let (base, server) = stub_server::start(vec![StubResponse::json(
+            401,
+            json!({"error": {"message": "bad key"}}),
+        )]);
+        let client = reqwest::Client::new();
+        let policy = RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(1),
+        };
+        let err = send_json("test", policy, || client.post(&base).json(&json!({})))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GenError::Auth { status: 401, .. }));
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+}
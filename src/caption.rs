@@ -0,0 +1,161 @@
+// Подгонка подписи под лимиты Telegram: длина считается в UTF-16 единицах,
+// разрез — по границе абзаца или предложения.
+use serde::Deserialize;
+
+/// Лимит подписи к фото.
+pub const CAPTION_LIMIT: usize = 1024;
+/// Лимит обычного текстового сообщения.
+pub const MESSAGE_LIMIT: usize = 4096;
+
+/// Что делать с текстом, не поместившимся в подпись.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OverflowMode {
+    /// Отбросить хвост.
+    #[default]
+    Drop,
+    /// Отправить хвост следующим сообщением в канал.
+    Message,
+    /// Отправить хвост ответом на пост с фото.
+    Reply,
+}
+
+/// Длина строки так, как её считает Telegram (UTF-16 code units).
+pub fn utf16_len(s: &str) -> usize {
+    s.encode_utf16().count()
+}
+
+/// Место разреза текста, не помещающегося в лимит.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cut {
+    /// Конец первой части (байтовая позиция).
+    pub head_end: usize,
+    /// Начало остатка (байтовая позиция).
+    pub tail_start: usize,
+    /// Разрез посреди предложения: к первой части нужно добавить «…».
+    pub ellipsis: bool,
+}
+
+/// Находит место разреза для `text`, чтобы первая часть (вместе с «…»,
+/// если она нужна) поместилась в `limit`. `None` — текст помещается целиком.
+/// Разрез делается по концу абзаца или предложения, иначе по пробелу.
+pub fn cut_point(text: &str, limit: usize) -> Option<Cut> {
+    if utf16_len(text) <= limit {
+        return None;
+    }
+    // Самый длинный префикс (по границам символов), влезающий в лимит с учётом «…»
+    let budget = limit.saturating_sub(1);
+    let mut units = 0;
+    let mut max_end = 0;
+    for (idx, ch) in text.char_indices() {
+        units += ch.len_utf16();
+        if units > budget {
+            break;
+        }
+        max_end = idx + ch.len_utf8();
+    }
+    let prefix = &text[..max_end];
+
+    if let Some(cut) = sentence_cut(prefix) {
+        return Some(trim_cut(text, cut, false));
+    }
+    let cut = prefix
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map(|(i, _)| i)
+        .filter(|&i| i > 0)
+        .unwrap_or(max_end);
+    Some(trim_cut(text, cut, true))
+}
+
+/// Сдвигает границы разреза так, чтобы пробелы не попали ни в одну из частей.
+fn trim_cut(text: &str, cut: usize, ellipsis: bool) -> Cut {
+    let head_end = text[..cut].trim_end().len();
+    let tail_start = text.len() - text[cut..].trim_start().len();
+    Cut {
+        head_end,
+        tail_start,
+        ellipsis,
+    }
+}
+
+/// Байтовая позиция конца последнего абзаца или предложения в `prefix`
+/// (не ближе трети текста от начала, чтобы не оставлять огрызок).
+fn sentence_cut(prefix: &str) -> Option<usize> {
+    let min = prefix.len() / 3;
+    if let Some(pos) = prefix.rfind("\n\n").filter(|&p| p >= min) {
+        return Some(pos);
+    }
+    let chars: Vec<(usize, char)> = prefix.char_indices().collect();
+    for w in (0..chars.len()).rev() {
+        let (idx, ch) = chars[w];
+        if idx < min {
+            break;
+        }
+        if matches!(ch, '.' | '!' | '?' | '…') {
+            let end = idx + ch.len_utf8();
+            let at_boundary = chars
+                .get(w + 1)
+                .is_none_or(|(_, next)| next.is_whitespace());
+            if at_boundary && end < prefix.len() {
+                return Some(end);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Formatted;
+
+    /// Разрез так, как его делает публикация: `Formatted::split` по видимому тексту.
+    fn split(text: &str, limit: usize) -> (String, Option<String>) {
+        let (head, tail) = Formatted::plain(text).split(limit);
+        (head.visible(), tail.map(|t| t.visible()))
+    }
+
+    #[test]
+    fn counts_utf16_units() {
+        assert_eq!(utf16_len("акварель"), 8);
+        assert_eq!(utf16_len("🌸"), 2);
+    }
+
+    #[test]
+    fn short_text_is_untouched() {
+        assert_eq!(split("  Пионы.  ", 20), ("Пионы.".to_string(), None));
+    }
+
+    #[test]
+    fn cuts_at_sentence_boundary() {
+        let text = "Первое предложение. Второе предложение. Третье предложение.";
+        let (head, tail) = split(text, 45);
+        assert_eq!(head, "Первое предложение. Второе предложение.");
+        assert_eq!(tail.as_deref(), Some("Третье предложение."));
+    }
+
+    #[test]
+    fn prefers_paragraph_break() {
+        let text = "Абзац один. Ещё фраза.\n\nАбзац два длинный-длинный.";
+        let (head, tail) = split(text, 30);
+        assert_eq!(head, "Абзац один. Ещё фраза.");
+        assert_eq!(tail.as_deref(), Some("Абзац два длинный-длинный."));
+    }
+
+    #[test]
+    fn falls_back_to_word_boundary_with_ellipsis() {
+        let (head, tail) = split("слово слово слово слово", 13);
+        assert_eq!(head, "слово слово…");
+        assert_eq!(tail.as_deref(), Some("слово слово"));
+        assert!(utf16_len(&head) <= 13);
+    }
+
+    #[test]
+    fn emoji_are_counted_as_two_units() {
+        let text = "🌸".repeat(10);
+        let (head, _) = split(&text, 9);
+        assert!(utf16_len(&head) <= 9);
+    }
+}
@@ -0,0 +1,680 @@
+// Получение подписи для фото: выбор персоны и сборка промпта, запрос к
+// провайдеру и проверка результата.
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use anyhow::{bail, Result};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+
+use crate::config::Config;
+use crate::critique;
+use crate::db::{CaptionMeta, Db};
+use crate::examples;
+use crate::fallback;
+use crate::format::CaptionFormat;
+use crate::generator;
+use crate::hashtags;
+use crate::logging::{compact, log, Level};
+use crate::palette::dominant_colors;
+use crate::policy::{self, Violation};
+use crate::prompts::{active_persona, load_persona, system_prompt_for, PromptVars};
+use crate::provider::{CaptionProvider, CaptionRequest};
+use crate::repetition::{self, Similarity};
+use crate::sidecar::Sidecar;
+use crate::structured;
+use crate::usage;
+use crate::vision;
+
+/// Подсказка модели о доступной разметке, если подпись публикуется с оформлением.
+const FORMAT_HINT: &str = "
+Оформление: название картины выдели **жирным**, подпись художницы — *курсивом*, \
+ссылки пиши как [текст](https://адрес). Другую разметку не используй.";
+
+/// Что нужно знать о публикации, чтобы сгенерировать для неё подпись.
+pub struct CaptionJob<'a> {
+    pub image: &'a [u8],
+    /// SHA-256 изображения, если уже посчитан (иначе считается по `image`).
+    pub image_hash: Option<String>,
+    pub channel_id: i64,
+    /// Публикация по расписанию (влияет на выбор персоны).
+    pub scheduled: bool,
+    /// Данные о работе из сопроводительного файла.
+    pub sidecar: Sidecar,
+    /// Персона, выбранная для конкретного черновика (важнее остальных настроек).
+    pub persona: Option<String>,
+    /// Не брать подпись из кэша, а сгенерировать заново.
+    pub fresh: bool,
+    /// Подпись, присланная вместе с фото.
+    pub note: Option<UploaderNote>,
+}
+
+/// Подпись, которую художница прислала вместе с фото.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UploaderNote {
+    /// Обычный текст — подсказка модели: название, что вдохновило и т. п.
+    Hint(String),
+    /// `!текст` — опубликовать текст как есть, без генерации.
+    Verbatim(String),
+    /// `+текст` — дописать текст после сгенерированной истории.
+    Append(String),
+}
+
+impl UploaderNote {
+    /// Разбирает подпись по префиксу; пустой текст — `None`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let (make, rest): (fn(String) -> Self, &str) = if let Some(rest) = text.strip_prefix('!') {
+            (UploaderNote::Verbatim, rest)
+        } else if let Some(rest) = text.strip_prefix('+') {
+            (UploaderNote::Append, rest)
+        } else {
+            (UploaderNote::Hint, text)
+        };
+        let rest = rest.trim();
+        (!rest.is_empty()).then(|| make(rest.to_string()))
+    }
+
+    /// Та же подпись как подсказка модели (для перегенерации по кнопке).
+    pub fn into_hint(self) -> Self {
+        match self {
+            UploaderNote::Verbatim(text) => UploaderNote::Hint(text),
+            other => other,
+        }
+    }
+}
+
+/// Генерирует до `n` вариантов подписи (см. `caption_variants_mode`); пустые
+/// варианты отбрасываются, а без подписи вовсе возвращается ошибка, чтобы пост
+/// не ушёл в канал без текста. Результат кэшируется по хэшу изображения, ключу
+/// промпта (см. [`cache_key`]) и имени провайдера: повторная обработка той же
+/// картинки не стоит запроса.
+/// При `structured_output` ответ модели — JSON по схеме, из которого подпись
+/// собирается по `caption_layout`, а название и описание сохраняются отдельно.
+/// Если задан словарь хэштегов, к каждой подписи добавляется строка тегов.
+/// При `critique` каждый вариант проверяется критиком по правилам персоны.
+/// Подписи, слишком похожие на последние публикации, генерируются заново с
+/// подсказкой не повторяться (до `repetition_retries` раз), нарушающие
+/// `caption_policy` — с подсказкой, что исправить (до `caption_policy.retries` раз).
+/// Подпись художницы с `!` возвращается как есть, с `+` — дописывается к истории.
+/// Если месячный бюджет исчерпан, возвращается запасная подпись без критика и
+/// повторов.
+pub async fn generate_variants(
+    config: &Config,
+    db: &Db,
+    provider: &dyn CaptionProvider,
+    job: &CaptionJob<'_>,
+    n: usize,
+) -> Result<Vec<String>> {
+    if let Some(UploaderNote::Verbatim(text)) = &job.note {
+        log("ai", "caption", Level::Info, "Подпись художницы публикуется как есть")
+            .data("len", text.len().to_string())
+            .print();
+        return Ok(vec![text.clone()]);
+    }
+    let dict = config.hashtags.as_ref();
+    let vars = prompt_vars(job).await;
+    let image_hash = job
+        .image_hash
+        .clone()
+        .unwrap_or_else(|| sha256_hex(job.image));
+    if usage::budget_exhausted(config, db).await? {
+        log(
+            "ai",
+            "usage",
+            Level::Warn,
+            "Месячный бюджет исчерпан, используем запасную подпись",
+        )
+        .print();
+        let persona = job_persona(config, db, job).await?;
+        let seed = fallback::seed(image_hash.as_bytes());
+        let text = fallback::render(config, persona.as_deref(), &vars, seed);
+        return Ok(vec![with_hashtags(config, job, &vars, text)]);
+    }
+    let (persona, mut system) = build_prompt(config, db, job, &vars).await?;
+    let prepared = Prepared {
+        image_hash,
+        cache_key: cache_key(config, persona.as_deref(), job.note.as_ref()),
+        persona,
+        vars,
+    };
+    // Правила для критика — промпт персоны без примеров и служебных подсказок
+    let rules = system.clone();
+    system.push_str(&examples::prompt_block(config, db).await?);
+    // Указания о формате ответа идут в конце промпта, после подсказки о повторах
+    let mut tail = String::new();
+    if config.structured_output {
+        tail.push_str(structured::PROMPT_HINT);
+    }
+    if let Some(dict) = dict {
+        tail.push_str(&hashtags::prompt_hint(
+            config,
+            dict,
+            config.structured_output,
+        ));
+    }
+    let recent = repetition::recent(config, db).await?;
+    // Кэш проверяется только до первой попытки: повторы с подсказкой всегда идут к модели
+    let mut cached = if config.caption_cache && !job.fresh {
+        cached_captions(db, provider, &prepared, n).await?
+    } else {
+        None
+    };
+    // Изображение для модели готовится один раз на все попытки (и не готовится,
+    // если подпись нашлась в кэше)
+    let mut image = None;
+    let mut hint = String::new();
+    let mut attempt = 0;
+    let assembled = loop {
+        let prompt = format!("{}{}{}", system, hint, tail);
+        let raw = match cached.take() {
+            Some(raw) => raw,
+            None => {
+                if image.is_none() {
+                    image = Some(vision::prepare(config, job.image).await);
+                }
+                let image = image.as_deref().unwrap_or(job.image);
+                generate_raw(config, db, provider, image, n, &prepared, &prompt).await?
+            }
+        };
+        let assembled = if config.structured_output {
+            assemble(config, raw)?
+        } else {
+            raw.into_iter().map(|c| (c, None)).collect()
+        };
+        let mut scored = Vec::with_capacity(assembled.len());
+        for (text, meta) in assembled {
+            let text = match critique::review(config, db, provider, &rules, &text).await {
+                Ok(reviewed) => reviewed,
+                Err(err) => {
+                    log(
+                        "ai",
+                        "critique",
+                        Level::Warn,
+                        "Критик не ответил, оставляем черновик",
+                    )
+                    .data("error", format!("{:#}", err))
+                    .print();
+                    text
+                }
+            };
+            let similarity = repetition::compare(&text, &recent);
+            log_similarity(&similarity, attempt);
+            let text = match &job.note {
+                Some(UploaderNote::Append(extra)) => format!("{}\n\n{}", text.trim_end(), extra),
+                _ => text,
+            };
+            let text = with_hashtags(config, job, &prepared.vars, text);
+            let violations = check_policy(config, &text, job.note.as_ref())?;
+            log_violations(&violations, attempt);
+            scored.push((text, meta, similarity, violations));
+        }
+        let (clean, violating): (Vec<_>, Vec<_>) = scored
+            .into_iter()
+            .partition(|(_, _, _, v)| v.is_empty());
+        let (fresh, repeats): (Vec<_>, Vec<_>) = clean
+            .into_iter()
+            .partition(|(_, _, s, _)| !s.is_repeat(config));
+        if !fresh.is_empty() {
+            break fresh;
+        }
+        let retries = if repeats.is_empty() {
+            config.caption_policy.retries
+        } else {
+            config.repetition_retries
+        };
+        if attempt >= retries {
+            // Лучше похожая подпись, чем никакой: берём наименее похожие. Подписи с
+            // нарушениями остаются последним вариантом — их не публикуют без утверждения
+            if repeats.is_empty() {
+                break violating;
+            }
+            let mut repeats = repeats;
+            repeats.sort_by(|a, b| a.2.score.total_cmp(&b.2.score));
+            break repeats;
+        }
+        hint = repetition::hint(&repeats.iter().map(|(_, _, s, _)| s).collect::<Vec<_>>());
+        if let Some((_, _, _, violations)) = violating.first() {
+            hint.push_str(&policy::hint(violations));
+        }
+        attempt += 1;
+    };
+    let mut captions = Vec::with_capacity(assembled.len());
+    for (text, meta, _, _) in assembled {
+        if let Some(meta) = meta {
+            db.put_caption_meta(&sha256_hex(text.as_bytes()), meta)
+                .await?;
+        }
+        captions.push(text);
+    }
+    Ok(captions)
+}
+
+/// Пишет в лог сходство подписи с последними публикациями.
+fn log_similarity(similarity: &Similarity, attempt: usize) {
+    log(
+        "ai",
+        "repetition",
+        Level::Info,
+        "Сходство с прошлыми подписями",
+    )
+    .data("score", format!("{:.3}", similarity.score))
+    .data(
+        "post_id",
+        similarity
+            .post_id
+            .map(|id| id.to_string())
+            .unwrap_or_else(|| "-".to_string()),
+    )
+    .data("same_opening", similarity.same_opening.to_string())
+    .data("attempt", attempt.to_string())
+    .print();
+}
+
+/// Нарушения `caption_policy` в подписи без того, что повторная генерация не
+/// исправит: текста, дописанного художницей через `+`, и строк из одних
+/// хэштегов. Подпись с `!` публикуется как есть и не проверяется.
+pub fn check_policy(
+    config: &Config,
+    caption: &str,
+    note: Option<&UploaderNote>,
+) -> Result<Vec<Violation>> {
+    let caption = match note {
+        Some(UploaderNote::Verbatim(_)) => return Ok(Vec::new()),
+        Some(UploaderNote::Append(extra)) => caption.replacen(extra.as_str(), "", 1),
+        _ => caption.to_string(),
+    };
+    let text = caption
+        .lines()
+        .filter(|line| {
+            let mut words = line.split_whitespace().peekable();
+            words.peek().is_none() || !words.all(hashtags::is_hashtag)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    policy::check(config, &text)
+}
+
+/// Пишет в лог нарушения `caption_policy`, если они есть.
+fn log_violations(violations: &[Violation], attempt: usize) {
+    if violations.is_empty() {
+        return;
+    }
+    log(
+        "ai",
+        "policy",
+        Level::Warn,
+        "Подпись не прошла проверку",
+    )
+    .data("violations", policy::describe(violations))
+    .data("attempt", attempt.to_string())
+    .print();
+}
+
+/// Собирает подписи из структурированных ответов по `caption_layout`. Ответы не
+/// по схеме отбрасываются; обычный текст (шаблон, провайдер без поддержки
+/// схемы) остаётся как есть, без отдельных полей.
+fn assemble(config: &Config, raw: Vec<String>) -> Result<Vec<(String, Option<CaptionMeta>)>> {
+    let mut out = Vec::new();
+    for text in raw {
+        if !text.trim_start().starts_with('{') {
+            out.push((text, None));
+            continue;
+        }
+        match structured::parse(&text) {
+            Ok(caption) => out.push((caption.render(&config.caption_layout), Some(caption.meta()))),
+            Err(err) => {
+                log(
+                    "ai",
+                    "structured",
+                    Level::Warn,
+                    "Ответ модели не прошёл проверку схемы",
+                )
+                .data("error", format!("{:#}", err))
+                .data("result", compact(&text, 160))
+                .print();
+            }
+        }
+    }
+    if out.is_empty() {
+        bail!("ни один ответ модели не соответствует схеме подписи");
+    }
+    Ok(out)
+}
+
+/// Данные публикации, которые готовятся один раз до повторов генерации.
+struct Prepared {
+    persona: Option<String>,
+    vars: PromptVars,
+    /// SHA-256 изображения.
+    image_hash: String,
+    /// Ключ промпта для кэша подписей.
+    cache_key: String,
+}
+
+/// Ключ кэша подписей: персона и текст её шаблона (без подставленных
+/// переменных), оформление, подсказка художницы и параметры генерации. Дата,
+/// примеры стиля и подсказки повторных попыток в ключ не входят, поэтому
+/// отложенный повтор или повторная обработка той же картинки попадает в кэш.
+fn cache_key(config: &Config, persona: Option<&str>, note: Option<&UploaderNote>) -> String {
+    let template = persona
+        .and_then(|name| load_persona(config, name).ok())
+        .unwrap_or_else(|| generator::system_prompt(config));
+    let hint = match note {
+        Some(UploaderNote::Hint(hint)) => hint.as_str(),
+        _ => "",
+    };
+    let model_params: BTreeMap<_, _> = config.model_params.iter().collect();
+    let key = format!(
+        "{}\n{}\n{:?}\n{}\n{}\n{:?}\n{:?}",
+        persona.unwrap_or("-"),
+        template,
+        config.caption_format,
+        config.structured_output,
+        hint,
+        config.persona_params(persona),
+        model_params,
+    );
+    sha256_hex(key.as_bytes())
+}
+
+/// Подписи из кэша для изображения, ключа промпта и провайдера (не меньше `n`).
+async fn cached_captions(
+    db: &Db,
+    provider: &dyn CaptionProvider,
+    prepared: &Prepared,
+    n: usize,
+) -> Result<Option<Vec<String>>> {
+    let n = n.max(1);
+    let model = provider.name();
+    let Some(mut cached) = db
+        .get_cached_captions(&prepared.image_hash, &prepared.cache_key, &model)
+        .await?
+        .filter(|c| c.len() >= n)
+    else {
+        return Ok(None);
+    };
+    cached.truncate(n);
+    log("ai", "cache", Level::Info, "Подпись взята из кэша")
+        .data("provider", model)
+        .data("image", compact(&prepared.image_hash, 12))
+        .data("count", cached.len().to_string())
+        .print();
+    Ok(Some(cached))
+}
+
+/// Подписи от провайдера без постобработки; результат сохраняется в кэш.
+/// `image` — изображение, уже подготовленное для Vision-запроса.
+async fn generate_raw(
+    config: &Config,
+    db: &Db,
+    provider: &dyn CaptionProvider,
+    image: &[u8],
+    n: usize,
+    prepared: &Prepared,
+    system: &str,
+) -> Result<Vec<String>> {
+    let persona = prepared.persona.as_deref();
+    let n = n.max(1);
+    let model = provider.name();
+    let request = CaptionRequest {
+        image,
+        system_prompt: system,
+        params: config.persona_params(persona),
+        structured: config.structured_output,
+    };
+    let started = Instant::now();
+    let generations = if n == 1 {
+        vec![provider.generate(&request).await?]
+    } else {
+        provider.generate_variants(&request, n).await?
+    };
+    let latency_ms = started.elapsed().as_millis() as u64;
+    for used in generations.iter().filter_map(|g| g.usage.as_ref()) {
+        usage::record(config, db, used, latency_ms).await?;
+    }
+    let captions: Vec<String> = generations
+        .into_iter()
+        .map(|g| g.text)
+        .filter(|c| !c.trim().is_empty())
+        .collect();
+    if captions.is_empty() {
+        bail!("провайдер {} вернул пустую подпись", model);
+    }
+    log("ai", "caption", Level::Info, "Подпись сгенерирована")
+        .data("provider", model.clone())
+        .data("persona", persona.unwrap_or("-"))
+        .data("count", captions.len().to_string())
+        .data("len", captions[0].len().to_string())
+        .data("result", compact(&captions[0], 160))
+        .print();
+    if config.caption_cache {
+        db.put_cached_captions(&prepared.image_hash, &prepared.cache_key, &model, &captions)
+            .await?;
+    }
+    Ok(captions)
+}
+
+/// SHA-256 в шестнадцатеричном виде.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Персона публикации и системный промпт для неё.
+async fn build_prompt(
+    config: &Config,
+    db: &Db,
+    job: &CaptionJob<'_>,
+    vars: &PromptVars,
+) -> Result<(Option<String>, String)> {
+    let persona = job_persona(config, db, job).await?;
+    let mut system = system_prompt_for(config, persona.as_deref(), vars);
+    if config.caption_format != CaptionFormat::Plain {
+        system.push_str(FORMAT_HINT);
+    }
+    if let Some(UploaderNote::Hint(hint)) = &job.note {
+        system.push_str(&format!(
+            "\n\nХудожница написала к этой работе (название, что вдохновило и т. п.), \
+             учти это в истории: «{}»",
+            hint
+        ));
+    }
+    Ok((persona, system))
+}
+
+/// Персона публикации: выбранная для черновика или активная для канала.
+async fn job_persona(config: &Config, db: &Db, job: &CaptionJob<'_>) -> Result<Option<String>> {
+    match &job.persona {
+        Some(p) => Ok(Some(p.clone())),
+        None => active_persona(config, db, job.channel_id, job.scheduled).await,
+    }
+}
+
+/// Запасная подпись без модели (`fallback_templates` и пулы фраз персоны) с
+/// хэштегами из словаря, если он есть.
+pub async fn fallback_caption(config: &Config, db: &Db, job: &CaptionJob<'_>) -> Result<String> {
+    let persona = job_persona(config, db, job).await?;
+    let vars = prompt_vars(job).await;
+    let text = fallback::render(config, persona.as_deref(), &vars, fallback::seed(job.image));
+    log("ai", "fallback", Level::Info, "Собрана запасная подпись")
+        .data("persona", persona.as_deref().unwrap_or("-"))
+        .data("result", compact(&text, 160))
+        .print();
+    Ok(with_hashtags(config, job, &vars, text))
+}
+
+/// Подпись со строкой хэштегов из словаря, если он задан.
+fn with_hashtags(config: &Config, job: &CaptionJob<'_>, vars: &PromptVars, text: String) -> String {
+    match &config.hashtags {
+        Some(dict) => hashtags::apply(config, dict, &text, &job.sidecar.tags, &vars.palette),
+        None => text,
+    }
+}
+
+/// Переменные промпта: данные из сопроводительного файла, палитра и текущая дата.
+/// Палитра считается по всему изображению, поэтому декодирование идёт в
+/// отдельном потоке, не занимая рантайм.
+async fn prompt_vars(job: &CaptionJob<'_>) -> PromptVars {
+    let image = job.image.to_vec();
+    let palette = tokio::task::spawn_blocking(move || dominant_colors(&image, 3))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|r| r)
+        .unwrap_or_else(|err| {
+            log(
+                "ai",
+                "palette",
+                Level::Debug,
+                "Не удалось определить палитру",
+            )
+            .data("error", err.to_string())
+            .print();
+            Vec::new()
+        });
+    PromptVars {
+        title: job.sidecar.title.clone().unwrap_or_default(),
+        series: job.sidecar.series.clone().unwrap_or_default(),
+        palette: palette.into_iter().map(str::to_string).collect(),
+        date: OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use crate::db::PostRecord;
+    use crate::provider::{BoxFuture, Generation};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Провайдер, который считает запросы.
+    struct Counting(AtomicUsize);
+
+    impl CaptionProvider for Counting {
+        fn name(&self) -> String {
+            "counting".to_string()
+        }
+
+        fn generate<'a>(
+            &'a self,
+            _req: &'a CaptionRequest<'a>,
+        ) -> BoxFuture<'a, Result<Generation>> {
+            let call = self.0.fetch_add(1, Ordering::SeqCst);
+            let text = format!("Пионы в утреннем свете, вариант {}.", call + 1);
+            Box::pin(async move { Ok(Generation::text(text)) })
+        }
+    }
+
+    #[tokio::test]
+    async fn same_image_hits_cache_after_examples_change() {
+        let config = test_config(serde_json::json!({"style_examples": 3}));
+        let db = Db::open(":memory:").await.unwrap();
+        let provider = Counting(AtomicUsize::new(0));
+        let job = CaptionJob {
+            image: b"not really an image",
+            image_hash: None,
+            channel_id: 1,
+            scheduled: true,
+            sidecar: Sidecar::default(),
+            persona: None,
+            fresh: false,
+            note: None,
+        };
+        let first = generate_variants(&config, &db, &provider, &job, 1)
+            .await
+            .unwrap();
+        // Новая публикация меняет примеры стиля в промпте, но не ключ кэша
+        db.log_post(PostRecord {
+            channel_id: 1,
+            message_id: Some(1),
+            file_id: None,
+            caption: Some("Совсем другая история о розах у реки.".to_string()),
+            translation: None,
+            title: None,
+            description: None,
+        })
+        .await
+        .unwrap();
+        let second = generate_variants(&config, &db, &provider, &job, 1)
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+        assert_eq!(provider.0.load(Ordering::SeqCst), 1);
+        let fresh = CaptionJob { fresh: true, ..job };
+        let third = generate_variants(&config, &db, &provider, &fresh, 1)
+            .await
+            .unwrap();
+        assert_ne!(third, first);
+        assert_eq!(provider.0.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn exhausted_budget_returns_fallback_without_model_calls() {
+        let config = test_config(serde_json::json!({
+            "monthly_budget_usd": 0.0,
+            "critique": true,
+            "caption_variants": 3
+        }));
+        let db = Db::open(":memory:").await.unwrap();
+        let provider = Counting(AtomicUsize::new(0));
+        let job = CaptionJob {
+            image: b"not really an image",
+            image_hash: None,
+            channel_id: 1,
+            scheduled: true,
+            sidecar: Sidecar::default(),
+            persona: None,
+            fresh: false,
+            note: None,
+        };
+        let captions = generate_variants(&config, &db, &provider, &job, 3)
+            .await
+            .unwrap();
+        assert_eq!(captions.len(), 1);
+        assert!(!captions[0].trim().is_empty());
+        assert_eq!(provider.0.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn policy_skips_appendix_and_hashtags() {
+        let config = test_config(serde_json::json!({
+            "caption_policy": {"banned_words": ["дёшево"], "max_emoji": 0}
+        }));
+        let extra = "Watercolor on paper, 30×40 cm 🌸";
+        let caption = format!(
+            "Пионы в утреннем свете.\n\n{}\n\n#watercolor #flowers",
+            extra
+        );
+        let note = UploaderNote::Append(extra.to_string());
+        assert!(check_policy(&config, &caption, Some(&note))
+            .unwrap()
+            .is_empty());
+        assert!(!check_policy(&config, &caption, None).unwrap().is_empty());
+        let note = UploaderNote::Append("Размер 30×40".to_string());
+        assert_eq!(
+            check_policy(&config, "Дёшево!\n\nРазмер 30×40", Some(&note)).unwrap(),
+            vec![Violation::BannedWord("дёшево".to_string())]
+        );
+    }
+
+    #[test]
+    fn parses_uploader_note_prefixes() {
+        assert_eq!(
+            UploaderNote::parse(" Пионы с дачи "),
+            Some(UploaderNote::Hint("Пионы с дачи".to_string()))
+        );
+        assert_eq!(
+            UploaderNote::parse("! Готовый текст"),
+            Some(UploaderNote::Verbatim("Готовый текст".to_string()))
+        );
+        assert_eq!(
+            UploaderNote::parse("+Размер 30×40"),
+            Some(UploaderNote::Append("Размер 30×40".to_string()))
+        );
+        assert_eq!(UploaderNote::parse("  !  "), None);
+        assert_eq!(
+            UploaderNote::parse("!текст").map(UploaderNote::into_hint),
+            Some(UploaderNote::Hint("текст".to_string()))
+        );
+    }
+}
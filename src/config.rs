@@ -1,7 +1,20 @@
 use anyhow::{Context, Result};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs;
 
+use crate::caption::OverflowMode;
+use crate::crop::CropSpec;
+use crate::format::CaptionFormat;
+use crate::gen_params::{self, GenParams, ImageDetail};
+use crate::generator::{OpenAiApi, OpenAiAuth};
+use crate::hashtags::{self, Dictionary};
+use crate::policy::{self, CaptionPolicy};
+use crate::pool::PoolEntry;
+use crate::structured;
+use crate::translate::Language;
+use crate::usage::ModelPrice;
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
     #[serde(alias = "TELOXIDE_TOKEN", alias = "teloxide_token")]
@@ -26,14 +39,312 @@ pub struct Config {
     pub openai_model: String,
     #[serde(alias = "OPENAI_BASE", alias = "openai_base", default = "default_openai_base")]
     pub openai_base: String,
+    #[serde(alias = "OPENAI_API", alias = "openai_api", default)]
+    pub openai_api: OpenAiApi,
+    /// Полный адрес запроса вместо `{openai_base}/v1/...` (Azure, шлюзы).
+    #[serde(alias = "OPENAI_ENDPOINT", alias = "openai_endpoint")]
+    pub openai_endpoint: Option<String>,
+    #[serde(alias = "OPENAI_AUTH", alias = "openai_auth", default)]
+    pub openai_auth: OpenAiAuth,
+    /// Параметр `api-version` в адресе запроса (Azure).
+    #[serde(alias = "OPENAI_API_VERSION", alias = "openai_api_version")]
+    pub openai_api_version: Option<String>,
+    /// Дополнительные заголовки каждого запроса к OpenAI.
+    #[serde(alias = "OPENAI_HEADERS", alias = "openai_headers", default)]
+    pub openai_headers: HashMap<String, String>,
     #[serde(alias = "OPENAI_USE_VISION", alias = "openai_use_vision")]
     pub openai_use_vision: Option<bool>,
     #[serde(alias = "OPENAI_VISION_MODEL", alias = "openai_vision_model")]
     pub openai_vision_model: Option<String>,
     #[serde(alias = "OPENAI_SYSTEM_PROMPT", alias = "openai_system_prompt")]
     pub openai_system_prompt: Option<String>,
+    #[serde(
+        alias = "AI_TIMEOUT_SECS",
+        alias = "ai_timeout_secs",
+        default = "default_ai_timeout_secs"
+    )]
+    pub ai_timeout_secs: u64,
+    #[serde(
+        alias = "AI_MAX_RETRIES",
+        alias = "ai_max_retries",
+        default = "default_ai_max_retries"
+    )]
+    pub ai_max_retries: u32,
+    #[serde(
+        alias = "AI_RETRY_BASE_MS",
+        alias = "ai_retry_base_ms",
+        default = "default_ai_retry_base_ms"
+    )]
+    pub ai_retry_base_ms: u64,
+    #[serde(
+        alias = "CAPTION_PROVIDER",
+        alias = "caption_provider",
+        default = "default_caption_provider"
+    )]
+    pub caption_provider: String,
+    #[serde(alias = "CAPTION_CHAIN", alias = "caption_chain", default)]
+    pub caption_chain: Vec<String>,
+    #[serde(alias = "PROVIDER_POOL", alias = "provider_pool", default)]
+    pub provider_pool: Vec<PoolEntry>,
+    #[serde(
+        alias = "POOL_COOLDOWN_SECS",
+        alias = "pool_cooldown_secs",
+        default = "default_pool_cooldown_secs"
+    )]
+    pub pool_cooldown_secs: u64,
+    #[serde(
+        alias = "POOL_QUOTA_COOLDOWN_SECS",
+        alias = "pool_quota_cooldown_secs",
+        default = "default_pool_quota_cooldown_secs"
+    )]
+    pub pool_quota_cooldown_secs: u64,
+    #[serde(alias = "OLLAMA_BASE", alias = "ollama_base", default = "default_ollama_base")]
+    pub ollama_base: String,
+    #[serde(alias = "OLLAMA_MODEL", alias = "ollama_model", default = "default_ollama_model")]
+    pub ollama_model: String,
+    #[serde(
+        alias = "CAPTION_TEMPLATE",
+        alias = "caption_template",
+        default = "default_caption_template"
+    )]
+    pub caption_template: String,
+    /// Шаблоны запасных подписей (пусто — встроенные).
+    #[serde(alias = "FALLBACK_TEMPLATES", alias = "fallback_templates", default)]
+    pub fallback_templates: Vec<String>,
+    /// Пулы фраз запасных подписей: персона (или `default`) → слот → фразы.
+    #[serde(alias = "FALLBACK_PHRASES", alias = "fallback_phrases", default)]
+    pub fallback_phrases: HashMap<String, HashMap<String, Vec<String>>>,
+    /// Подпись автора для слота `{signature}`.
+    #[serde(alias = "SIGNATURE", alias = "signature")]
+    pub signature: Option<String>,
+    /// Подвал, добавляемый к каждой подписи при публикации.
+    #[serde(alias = "CAPTION_FOOTER", alias = "caption_footer")]
+    pub caption_footer: Option<String>,
+    #[serde(alias = "CAPTION_FORMAT", alias = "caption_format", default)]
+    pub caption_format: CaptionFormat,
+    #[serde(alias = "CAPTION_OVERFLOW", alias = "caption_overflow", default)]
+    pub caption_overflow: OverflowMode,
+    #[serde(
+        alias = "CAPTION_VARIANTS",
+        alias = "caption_variants",
+        default = "default_caption_variants"
+    )]
+    pub caption_variants: usize,
+    #[serde(alias = "CAPTION_VARIANTS_MODE", alias = "caption_variants_mode", default)]
+    pub caption_variants_mode: VariantsMode,
+    #[serde(
+        alias = "CAPTION_CACHE",
+        alias = "caption_cache",
+        default = "default_caption_cache"
+    )]
+    pub caption_cache: bool,
+    #[serde(alias = "LANGUAGE", alias = "language", default)]
+    pub language: Language,
+    #[serde(alias = "STYLE_EXAMPLES", alias = "style_examples", default)]
+    pub style_examples: usize,
+    #[serde(alias = "STYLE_EXAMPLES_MODE", alias = "style_examples_mode", default)]
+    pub style_examples_mode: ExamplesMode,
+    #[serde(
+        alias = "REPETITION_WINDOW",
+        alias = "repetition_window",
+        default = "default_repetition_window"
+    )]
+    pub repetition_window: usize,
+    #[serde(
+        alias = "REPETITION_THRESHOLD",
+        alias = "repetition_threshold",
+        default = "default_repetition_threshold"
+    )]
+    pub repetition_threshold: f64,
+    #[serde(
+        alias = "REPETITION_RETRIES",
+        alias = "repetition_retries",
+        default = "default_repetition_retries"
+    )]
+    pub repetition_retries: usize,
+    #[serde(alias = "CRITIQUE", alias = "critique", default)]
+    pub critique: bool,
+    #[serde(
+        alias = "CRITIQUE_THRESHOLD",
+        alias = "critique_threshold",
+        default = "default_critique_threshold"
+    )]
+    pub critique_threshold: u8,
+    #[serde(alias = "CAPTION_POLICY", alias = "caption_policy", default)]
+    pub caption_policy: CaptionPolicy,
+    #[serde(alias = "STRUCTURED_OUTPUT", alias = "structured_output", default)]
+    pub structured_output: bool,
+    #[serde(
+        alias = "CAPTION_LAYOUT",
+        alias = "caption_layout",
+        default = "default_caption_layout"
+    )]
+    pub caption_layout: String,
+    #[serde(alias = "HASHTAGS_FILE", alias = "hashtags_file")]
+    pub hashtags_file: Option<String>,
+    #[serde(
+        alias = "HASHTAGS_MAX",
+        alias = "hashtags_max",
+        default = "default_hashtags_max"
+    )]
+    pub hashtags_max: usize,
+    /// Словарь из `hashtags_file`, загруженный при старте.
+    #[serde(skip)]
+    pub hashtags: Option<Dictionary>,
+    #[serde(
+        alias = "VISION_MAX_EDGE",
+        alias = "vision_max_edge",
+        default = "default_vision_max_edge"
+    )]
+    pub vision_max_edge: u32,
+    #[serde(
+        alias = "VISION_JPEG_QUALITY",
+        alias = "vision_jpeg_quality",
+        default = "default_vision_jpeg_quality"
+    )]
+    pub vision_jpeg_quality: u8,
+    #[serde(alias = "VISION_DETAIL", alias = "vision_detail")]
+    pub vision_detail: Option<ImageDetail>,
+    #[serde(alias = "MODEL_PARAMS", alias = "model_params", default)]
+    pub model_params: HashMap<String, GenParams>,
+    #[serde(alias = "PERSONA_PARAMS", alias = "persona_params", default)]
+    pub persona_params: HashMap<String, GenParams>,
+    #[serde(alias = "MODEL_PRICES", alias = "model_prices", default)]
+    pub model_prices: HashMap<String, ModelPrice>,
+    #[serde(alias = "MONTHLY_BUDGET_USD", alias = "monthly_budget_usd")]
+    pub monthly_budget_usd: Option<f64>,
+    #[serde(alias = "ADMIN_CHAT_ID", alias = "admin_chat_id")]
+    pub admin_chat_id: Option<i64>,
+    #[serde(
+        alias = "CAPTION_FAILURE_SCHEDULED",
+        alias = "caption_failure_scheduled",
+        default = "default_failure_scheduled"
+    )]
+    pub caption_failure_scheduled: FailurePolicy,
+    #[serde(
+        alias = "CAPTION_FAILURE_MANUAL",
+        alias = "caption_failure_manual",
+        default = "default_failure_manual"
+    )]
+    pub caption_failure_manual: FailurePolicy,
+    #[serde(
+        alias = "CAPTION_RETRY_SECS",
+        alias = "caption_retry_secs",
+        default = "default_caption_retry_secs"
+    )]
+    pub caption_retry_secs: u64,
+    #[serde(
+        alias = "REQUIRE_APPROVAL",
+        alias = "require_approval",
+        default = "default_require_approval"
+    )]
+    pub require_approval: bool,
+    #[serde(alias = "POST_REQUIRE_APPROVAL", alias = "post_require_approval", default)]
+    pub post_require_approval: bool,
     #[serde(alias = "LOG_LEVEL", alias = "log_level")]
     pub log_level: Option<String>,
+    #[serde(alias = "PROMPTS_DIR", alias = "prompts_dir")]
+    pub prompts_dir: Option<String>,
+    #[serde(alias = "PERSONA", alias = "persona")]
+    pub persona: Option<String>,
+    #[serde(alias = "POST_PERSONA", alias = "post_persona")]
+    pub post_persona: Option<String>,
+    #[serde(alias = "CROP", alias = "crop")]
+    pub crop: Option<CropSpec>,
+    #[serde(alias = "POST_CROP", alias = "post_crop")]
+    pub post_crop: Option<CropSpec>,
+    #[serde(alias = "CHANNELS", alias = "channels", default)]
+    pub channels: HashMap<String, ChannelConfig>,
+}
+
+/// Как получать несколько вариантов подписи.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum VariantsMode {
+    /// Одним запросом с параметром `n` (где провайдер это поддерживает).
+    #[default]
+    N,
+    /// Параллельными запросами с разной температурой.
+    Parallel,
+}
+
+/// Какие прошлые подписи брать в примеры стиля (после закреплённых).
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExamplesMode {
+    /// Самые новые публикации.
+    #[default]
+    Recent,
+    /// Публикации с наивысшей оценкой /rate.
+    Rated,
+}
+
+/// Что делать, если подпись не удалось сгенерировать.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FailurePolicy {
+    /// Не публиковать, повторить генерацию позже.
+    Defer,
+    /// Опубликовать с подписью из `caption_template`.
+    Template,
+    /// Попросить администратора прислать текст подписи.
+    Ask,
+}
+
+/// Настройки отдельного канала; ключ в `channels` — числовой ID канала строкой.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ChannelConfig {
+    #[serde(default)]
+    pub crop: Option<CropSpec>,
+    #[serde(default)]
+    pub persona: Option<String>,
+    #[serde(default)]
+    pub language: Option<Language>,
+    /// Подвал канала вместо `caption_footer` (пустая строка — без подвала).
+    #[serde(default)]
+    pub footer: Option<String>,
+}
+
+impl Config {
+    /// Настройки канала `channel_id` (пустые, если канал не описан в конфиге).
+    pub fn channel(&self, channel_id: i64) -> ChannelConfig {
+        self.channels
+            .get(&channel_id.to_string())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Вариант кадрирования для публикации в канал: для расписания сначала `post_crop`,
+    /// затем настройка канала, затем общий `crop`.
+    pub fn crop_for(&self, channel_id: i64, scheduled: bool) -> Option<CropSpec> {
+        let scheduled_crop = if scheduled { self.post_crop } else { None };
+        scheduled_crop
+            .or(self.channel(channel_id).crop)
+            .or(self.crop)
+    }
+
+    /// Параметры генерации персоны (пустые, если для неё ничего не задано).
+    pub fn persona_params(&self, persona: Option<&str>) -> GenParams {
+        persona
+            .and_then(|p| self.persona_params.get(p))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Язык публикаций канала: настройка канала или общий `language`.
+    pub fn language_for(&self, channel_id: i64) -> Language {
+        self.channel(channel_id).language.unwrap_or(self.language)
+    }
+
+    /// Нужно ли показать подпись на утверждение перед публикацией: для фото из
+    /// чата — `require_approval`, для расписания — `post_require_approval`.
+    pub fn approval_required(&self, scheduled: bool) -> bool {
+        if scheduled {
+            self.post_require_approval
+        } else {
+            self.require_approval
+        }
+    }
 }
 
 fn default_db_path() -> String {
@@ -52,10 +363,115 @@ fn default_openai_base() -> String {
     "https://api.openai.com".to_string()
 }
 
+fn default_ai_timeout_secs() -> u64 {
+    60
+}
+
+fn default_ai_max_retries() -> u32 {
+    3
+}
+
+fn default_ai_retry_base_ms() -> u64 {
+    1000
+}
+
+fn default_failure_scheduled() -> FailurePolicy {
+    FailurePolicy::Defer
+}
+
+fn default_failure_manual() -> FailurePolicy {
+    FailurePolicy::Ask
+}
+
+fn default_caption_retry_secs() -> u64 {
+    600
+}
+
+fn default_caption_variants() -> usize {
+    1
+}
+
+fn default_repetition_window() -> usize {
+    20
+}
+
+fn default_repetition_threshold() -> f64 {
+    0.25
+}
+
+fn default_repetition_retries() -> usize {
+    1
+}
+
+fn default_critique_threshold() -> u8 {
+    7
+}
+
+fn default_pool_cooldown_secs() -> u64 {
+    60
+}
+
+fn default_pool_quota_cooldown_secs() -> u64 {
+    3600
+}
+
+fn default_caption_layout() -> String {
+    structured::DEFAULT_LAYOUT.to_string()
+}
+
+fn default_hashtags_max() -> usize {
+    5
+}
+
+fn default_vision_max_edge() -> u32 {
+    1568
+}
+
+fn default_vision_jpeg_quality() -> u8 {
+    85
+}
+
+fn default_caption_cache() -> bool {
+    true
+}
+
+fn default_require_approval() -> bool {
+    true
+}
+
+fn default_caption_provider() -> String {
+    "openai".to_string()
+}
+
+fn default_ollama_base() -> String {
+    "http://localhost:11434".to_string()
+}
+
+fn default_ollama_model() -> String {
+    "llava".to_string()
+}
+
+fn default_caption_template() -> String {
+    "Новая акварельная работа. Пишите в личные сообщения, если она вам откликнулась.".to_string()
+}
+
 pub fn load_config(path: &str) -> Result<Config> {
     let raw = fs::read_to_string(path)
         .with_context(|| format!("не удалось прочитать config: {}", path))?;
-    let cfg: Config =
+    let mut cfg: Config =
         serde_json::from_str(&raw).with_context(|| format!("некорректный JSON: {}", path))?;
+    gen_params::validate(&cfg).context("некорректные параметры генерации")?;
+    policy::validate(&cfg).context("некорректная caption_policy")?;
+    cfg.hashtags = hashtags::load_dictionary(&cfg)?;
     Ok(cfg)
 }
+
+/// Конфиг для тестов: токен и поля из `overrides`.
+#[cfg(test)]
+pub fn test_config(overrides: serde_json::Value) -> Config {
+    let mut raw = serde_json::json!({"teloxide_token": "t"});
+    for (k, v) in overrides.as_object().expect("ожидается JSON-объект") {
+        raw[k] = v.clone();
+    }
+    serde_json::from_value(raw).unwrap()
+}
@@ -0,0 +1,154 @@
+// Второй проход модели (`critique`): критик оценивает черновик подписи по
+// правилам персоны (число абзацев, название в начале, строка эмодзи в конце
+// и т. п.) и при низкой оценке переписывает его. Оценка, причина и обе версии
+// сохраняются в таблицу `critiques`.
+use std::time::Instant;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::captioning::sha256_hex;
+use crate::config::Config;
+use crate::db::{Critique, Db};
+use crate::logging::{compact, log, Level};
+use crate::provider::CaptionProvider;
+use crate::usage;
+
+const CRITIC_PROMPT: &str = "Ты редактор подписей к постам о картинах. Тебе дают правила, \
+по которым автор пишет подписи, и черновик подписи. Оцени от 0 до 10, насколько черновик \
+следует правилам: число абзацев, название в начале, завершающая строка с эмодзи, стиль и тон. \
+Если правила нарушены, перепиши черновик так, чтобы он им следовал: сохрани историю, \
+разметку и хэштеги, ничего не выдумывай. Ответ верни JSON-объектом: score — оценка, \
+reason — коротко, что не так (или что хорошо), rewrite — исправленная подпись или null.";
+
+/// Ответ критика.
+#[derive(Debug, Deserialize)]
+struct Verdict {
+    score: f64,
+    reason: String,
+    #[serde(default)]
+    rewrite: Option<String>,
+}
+
+/// Разбирает ответ критика; JSON может быть обёрнут в блок кода.
+fn parse_verdict(text: &str) -> Result<Verdict> {
+    let (Some(start), Some(end)) = (text.find('{'), text.rfind('}')) else {
+        bail!("в ответе критика нет JSON");
+    };
+    if end < start {
+        bail!("в ответе критика нет JSON");
+    }
+    let verdict: Verdict =
+        serde_json::from_str(&text[start..=end]).context("ответ критика не по формату")?;
+    if !(0.0..=10.0).contains(&verdict.score) {
+        bail!("оценка критика вне диапазона: {}", verdict.score);
+    }
+    Ok(verdict)
+}
+
+/// Переписанная подпись, если оценка ниже порога и критик её предложил.
+fn rewrite_if_needed(config: &Config, verdict: &Verdict) -> Option<String> {
+    if verdict.score >= config.critique_threshold as f64 {
+        return None;
+    }
+    verdict
+        .rewrite
+        .as_deref()
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .map(str::to_string)
+}
+
+/// Проверяет черновик подписи по правилам `rules` (системный промпт персоны) и
+/// возвращает его или переписанный вариант. При `critique: false` и
+/// исчерпанном бюджете черновик возвращается без изменений; с `caption_cache`
+/// черновик, уже проверенный раньше, повторно критику не отправляется.
+pub async fn review(
+    config: &Config,
+    db: &Db,
+    provider: &dyn CaptionProvider,
+    rules: &str,
+    draft: &str,
+) -> Result<String> {
+    if !config.critique {
+        return Ok(draft.to_string());
+    }
+    let hash = sha256_hex(draft.as_bytes());
+    if config.caption_cache {
+        if let Some(critique) = db.last_critique(&hash).await? {
+            return Ok(critique.rewritten.unwrap_or(critique.original));
+        }
+    }
+    if usage::budget_exhausted(config, db).await? {
+        log(
+            "ai",
+            "critique",
+            Level::Warn,
+            "Месячный бюджет исчерпан, подпись без проверки критиком",
+        )
+        .print();
+        return Ok(draft.to_string());
+    }
+    let user = format!("Правила:\n{}\n\nЧерновик подписи:\n{}", rules.trim(), draft);
+    let started = Instant::now();
+    let generation = provider.complete(CRITIC_PROMPT, &user).await?;
+    if let Some(used) = &generation.usage {
+        usage::record(config, db, used, started.elapsed().as_millis() as u64).await?;
+    }
+    let verdict = parse_verdict(&generation.text)?;
+    let rewritten = rewrite_if_needed(config, &verdict);
+    log("ai", "critique", Level::Info, "Подпись оценена критиком")
+        .data("score", verdict.score.to_string())
+        .data("rewritten", rewritten.is_some().to_string())
+        .data("reason", compact(&verdict.reason, 160))
+        .print();
+    let critique = Critique {
+        score: verdict.score.round() as i64,
+        reason: verdict.reason.trim().to_string(),
+        original: draft.to_string(),
+        rewritten,
+        model: provider.name(),
+    };
+    let result = critique
+        .rewritten
+        .clone()
+        .unwrap_or_else(|| draft.to_string());
+    db.log_critique(&hash, critique).await?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+
+    fn config(threshold: u8) -> Config {
+        test_config(serde_json::json!({
+            "critique": true,
+            "critique_threshold": threshold
+        }))
+    }
+
+    #[test]
+    fn parses_fenced_verdict() {
+        let verdict = parse_verdict(
+            "```json\n{\"score\": 5, \"reason\": \"нет строки эмодзи\", \"rewrite\": \"Новый текст\"}\n```",
+        )
+        .unwrap();
+        assert_eq!(verdict.score, 5.0);
+        assert_eq!(
+            rewrite_if_needed(&config(7), &verdict).as_deref(),
+            Some("Новый текст")
+        );
+        assert_eq!(rewrite_if_needed(&config(5), &verdict), None);
+        assert!(parse_verdict("Всё хорошо").is_err());
+        assert!(parse_verdict("{\"score\": 42, \"reason\": \"\"}").is_err());
+    }
+
+    #[test]
+    fn keeps_draft_without_rewrite() {
+        let verdict =
+            parse_verdict("{\"score\": 3, \"reason\": \"плохо\", \"rewrite\": \" \"}").unwrap();
+        assert_eq!(rewrite_if_needed(&config(7), &verdict), None);
+    }
+}
@@ -0,0 +1,191 @@
+// Кадрирование под соцсети: приводит изображение к пропорции 1:1 или 4:5
+// обрезкой по центру либо добавлением полей (белых или в цвет бумаги).
+use anyhow::{Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::{imageops, Rgb, RgbImage};
+use serde::Deserialize;
+
+/// Целевая пропорция варианта.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum CropRatio {
+    #[serde(rename = "1:1", alias = "square")]
+    Square,
+    #[serde(rename = "4:5", alias = "portrait")]
+    Portrait,
+}
+
+impl CropRatio {
+    /// Пропорция в виде (ширина, высота).
+    fn parts(self) -> (u32, u32) {
+        match self {
+            CropRatio::Square => (1, 1),
+            CropRatio::Portrait => (4, 5),
+        }
+    }
+}
+
+/// Способ приведения к пропорции.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CropMode {
+    /// Обрезка по центру.
+    Crop,
+    /// Белые поля.
+    #[default]
+    Pad,
+    /// Поля в цвет бумаги (по краю картины).
+    Paper,
+}
+
+/// Вариант кадрирования из конфига: `{"ratio": "4:5", "mode": "paper"}`.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct CropSpec {
+    pub ratio: CropRatio,
+    #[serde(default)]
+    pub mode: CropMode,
+}
+
+/// Качество JPEG для обработанного варианта.
+const JPEG_QUALITY: u8 = 92;
+
+/// Применяет вариант кадрирования к байтам изображения.
+/// Возвращает `Ok(None)`, если изображение уже имеет нужную пропорцию.
+pub fn apply_crop(bytes: &[u8], spec: &CropSpec) -> Result<Option<Vec<u8>>> {
+    let img = image::load_from_memory(bytes)
+        .context("не удалось декодировать изображение")?
+        .to_rgb8();
+    let (w, h) = img.dimensions();
+    let (rw, rh) = spec.ratio.parts();
+    let out = match spec.mode {
+        CropMode::Crop => {
+            let Some((cw, ch)) = crop_dims(w, h, rw, rh) else {
+                return Ok(None);
+            };
+            imageops::crop_imm(&img, (w - cw) / 2, (h - ch) / 2, cw, ch).to_image()
+        }
+        CropMode::Pad | CropMode::Paper => {
+            let Some((pw, ph)) = pad_dims(w, h, rw, rh) else {
+                return Ok(None);
+            };
+            let color = match spec.mode {
+                CropMode::Paper => paper_color(&img),
+                _ => Rgb([255, 255, 255]),
+            };
+            let mut canvas = RgbImage::from_pixel(pw, ph, color);
+            imageops::replace(
+                &mut canvas,
+                &img,
+                ((pw - w) / 2) as i64,
+                ((ph - h) / 2) as i64,
+            );
+            canvas
+        }
+    };
+
+    let mut buf = Vec::new();
+    JpegEncoder::new_with_quality(&mut buf, JPEG_QUALITY)
+        .encode_image(&out)
+        .context("не удалось закодировать JPEG")?;
+    Ok(Some(buf))
+}
+
+/// Размеры центральной обрезки с пропорцией `rw:rh`, или `None`, если обрезать нечего.
+fn crop_dims(w: u32, h: u32, rw: u32, rh: u32) -> Option<(u32, u32)> {
+    let (w64, h64, rw64, rh64) = (w as u64, h as u64, rw as u64, rh as u64);
+    let (cw, ch) = if w64 * rh64 > h64 * rw64 {
+        ((h64 * rw64 / rh64) as u32, h)
+    } else {
+        (w, (w64 * rh64 / rw64) as u32)
+    };
+    if (cw, ch) == (w, h) || cw == 0 || ch == 0 {
+        None
+    } else {
+        Some((cw, ch))
+    }
+}
+
+/// Размеры холста с полями под пропорцию `rw:rh`, или `None`, если поля не нужны.
+fn pad_dims(w: u32, h: u32, rw: u32, rh: u32) -> Option<(u32, u32)> {
+    let (w64, h64, rw64, rh64) = (w as u64, h as u64, rw as u64, rh as u64);
+    let (pw, ph) = if w64 * rh64 > h64 * rw64 {
+        (w, (w64 * rh64).div_ceil(rw64) as u32)
+    } else {
+        ((h64 * rw64).div_ceil(rh64) as u32, h)
+    };
+    if (pw, ph) == (w, h) {
+        None
+    } else {
+        Some((pw, ph))
+    }
+}
+
+/// Цвет бумаги: медиана по каждому каналу в узкой полосе по краю картины.
+fn paper_color(img: &RgbImage) -> Rgb<u8> {
+    let (w, h) = img.dimensions();
+    let band = (w.min(h) / 50).max(1);
+    let mut channels: [Vec<u8>; 3] = [Vec::new(), Vec::new(), Vec::new()];
+    for (x, y, px) in img.enumerate_pixels() {
+        if x < band || y < band || x >= w.saturating_sub(band) || y >= h.saturating_sub(band) {
+            for (c, v) in channels.iter_mut().zip(px.0) {
+                c.push(v);
+            }
+        }
+    }
+    let median = |v: &mut Vec<u8>| -> u8 {
+        if v.is_empty() {
+            return 255;
+        }
+        v.sort_unstable();
+        v[v.len() / 2]
+    };
+    let [mut r, mut g, mut b] = channels;
+    Rgb([median(&mut r), median(&mut g), median(&mut b)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crop_dims_center_square() {
+        assert_eq!(crop_dims(1200, 800, 1, 1), Some((800, 800)));
+        assert_eq!(crop_dims(800, 1200, 4, 5), Some((800, 1000)));
+        assert_eq!(crop_dims(800, 800, 1, 1), None);
+    }
+
+    #[test]
+    fn pad_dims_extend_canvas() {
+        assert_eq!(pad_dims(1200, 800, 1, 1), Some((1200, 1200)));
+        assert_eq!(pad_dims(1000, 1000, 4, 5), Some((1000, 1250)));
+        assert_eq!(pad_dims(800, 1000, 4, 5), None);
+    }
+
+    #[test]
+    fn paper_color_matches_border() {
+        let mut img = RgbImage::from_pixel(100, 100, Rgb([240, 235, 220]));
+        for x in 20..80 {
+            for y in 20..80 {
+                img.put_pixel(x, y, Rgb([10, 40, 120]));
+            }
+        }
+        assert_eq!(paper_color(&img), Rgb([240, 235, 220]));
+    }
+
+    #[test]
+    fn apply_crop_produces_square_jpeg() {
+        let img = RgbImage::from_pixel(60, 40, Rgb([200, 10, 10]));
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgb8(img)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let spec = CropSpec {
+            ratio: CropRatio::Square,
+            mode: CropMode::Paper,
+        };
+        let out = apply_crop(&png, &spec)
+            .unwrap()
+            .expect("ожидается новый вариант");
+        let decoded = image::load_from_memory(&out).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (60, 60));
+    }
+}
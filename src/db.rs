@@ -1,6 +1,183 @@
 use anyhow::Result;
+use rusqlite::OptionalExtension;
 use tokio_rusqlite::Connection;
 
+/// Состояние черновика поста.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DraftStatus {
+    /// Ждём от администратора текст подписи ответом на сообщение бота.
+    AwaitingText,
+    /// Генерация не удалась, повторим позже в фоне.
+    Deferred,
+    /// Подпись готова и ждёт утверждения кнопками под предпросмотром.
+    Pending,
+    /// Ждём исправленный текст подписи ответом на сообщение бота.
+    Editing,
+    Published,
+    Cancelled,
+}
+
+impl DraftStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DraftStatus::AwaitingText => "awaiting_text",
+            DraftStatus::Deferred => "deferred",
+            DraftStatus::Pending => "pending",
+            DraftStatus::Editing => "editing",
+            DraftStatus::Published => "published",
+            DraftStatus::Cancelled => "cancelled",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "awaiting_text" => DraftStatus::AwaitingText,
+            "deferred" => DraftStatus::Deferred,
+            "pending" => DraftStatus::Pending,
+            "editing" => DraftStatus::Editing,
+            "published" => DraftStatus::Published,
+            "cancelled" => DraftStatus::Cancelled,
+            _ => return None,
+        })
+    }
+}
+
+/// Черновик поста: фото (по `file_id` из Telegram или по пути на диске)
+/// и канал назначения.
+#[derive(Debug, Clone)]
+pub struct Draft {
+    pub id: i64,
+    /// Чат, с которым ведётся диалог по черновику.
+    pub chat_id: i64,
+    pub channel_id: i64,
+    pub file_id: Option<String>,
+    pub path: Option<String>,
+    /// SHA-256 файла из папки (для учёта опубликованных файлов).
+    pub hash: Option<String>,
+    /// Черновик создан планировщиком (а не загрузкой в чат).
+    pub scheduled: bool,
+    /// Текущий вариант подписи (для черновиков на утверждении).
+    pub caption: Option<String>,
+    /// Персона, выбранная для черновика кнопкой; `None` — обычный выбор.
+    pub persona: Option<String>,
+    pub status: DraftStatus,
+    /// Сообщение бота с вопросом или предпросмотром.
+    pub prompt_message_id: Option<i32>,
+    /// Подпись, присланная вместе с фото (подсказка или текст для публикации).
+    pub uploader_note: Option<String>,
+}
+
+/// Данные для создания черновика.
+pub struct NewDraft {
+    pub chat_id: i64,
+    pub channel_id: i64,
+    pub file_id: Option<String>,
+    pub path: Option<String>,
+    pub hash: Option<String>,
+    pub status: DraftStatus,
+    pub scheduled: bool,
+    pub uploader_note: Option<String>,
+}
+
+const DRAFT_COLUMNS: &str =
+    "id, chat_id, channel_id, file_id, path, hash, scheduled, caption, persona, status, prompt_message_id, uploader_note";
+
+fn draft_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Draft> {
+    Ok(Draft {
+        id: row.get(0)?,
+        chat_id: row.get(1)?,
+        channel_id: row.get(2)?,
+        file_id: row.get(3)?,
+        path: row.get(4)?,
+        hash: row.get(5)?,
+        scheduled: row.get(6)?,
+        caption: row.get(7)?,
+        persona: row.get(8)?,
+        status: DraftStatus::parse(&row.get::<_, String>(9)?).unwrap_or(DraftStatus::Cancelled),
+        prompt_message_id: row.get(10)?,
+        uploader_note: row.get(11)?,
+    })
+}
+
+/// Добавляет столбец в существующую таблицу, если его ещё нет (миграция старых баз).
+fn ensure_column(conn: &rusqlite::Connection, table: &str, column: &str, decl: &str) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .iter()
+        .any(|name| name == column);
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl), [])?;
+    }
+    Ok(())
+}
+
+/// Запись о публикации для таблицы `posts`.
+pub struct PostRecord {
+    pub channel_id: i64,
+    pub message_id: Option<i64>,
+    pub file_id: Option<String>,
+    pub caption: Option<String>,
+    /// Перевод подписи и код его языка.
+    pub translation: Option<(String, String)>,
+    /// Название работы и описание изображения из структурированного ответа модели.
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Поля структурированного ответа модели, которые хранятся отдельно от текста подписи.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptionMeta {
+    pub title: String,
+    /// Описание изображения (alt-текст).
+    pub description: String,
+    pub price_min: Option<f64>,
+    pub price_max: Option<f64>,
+    pub currency: Option<String>,
+}
+
+/// Оценка подписи вторым проходом модели (критиком) и переписанный вариант.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Critique {
+    /// Оценка от 0 до 10.
+    pub score: i64,
+    /// Почему подпись нужно (или не нужно) переписать.
+    pub reason: String,
+    pub original: String,
+    /// Переписанная подпись, если оценка ниже `critique_threshold`.
+    pub rewritten: Option<String>,
+    pub model: String,
+}
+
+/// Расход одного обращения к модели.
+pub struct UsageRecord {
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub latency_ms: u64,
+    pub cost_usd: f64,
+}
+
+/// Суммарный расход за период.
+#[derive(Debug, Default)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+}
+
+/// Прошлая подпись из `posts`, которая может служить примером стиля.
+#[derive(Debug, Clone)]
+pub struct StyleExample {
+    pub id: i64,
+    pub caption: String,
+    pub rating: Option<i64>,
+    pub pinned: bool,
+    pub excluded: bool,
+}
+
 #[derive(Clone)]
 pub struct Db {
     conn: Connection,
@@ -16,9 +193,16 @@ impl Db {
     }
 
 /// Инициализирует схему БД (идемпотентно):
-/// - `config` — ключ/значение, хранит `channel_id`;
-/// - `posts`  — лог опубликованных сообщений;
-/// - `files`  — реестр обработанных файлов по хэшу SHA-256.
+/// - `config` — ключ/значение, хранит `channel_id` и выбранную персону;
+/// - `posts`  — лог опубликованных сообщений (с переводом подписи, если он был)
+///   и отметки для примеров стиля: оценка, закрепление, исключение;
+/// - `files`  — реестр обработанных файлов по хэшу SHA-256;
+/// - `drafts` — черновики постов: ожидающие подписи, утверждения или повторной генерации;
+/// - `caption_variants` — показанные варианты подписи и какой из них выбран;
+/// - `caption_cache` — сгенерированные подписи по хэшам изображения и промпта и модели;
+/// - `usage` — расход токенов, задержка и оценка стоимости каждой генерации;
+/// - `caption_meta` — название, описание и цена из структурированного ответа по хэшу текста подписи;
+/// - `critiques` — оценки подписей критиком, причина переписывания и обе версии.
     async fn init(&self) -> Result<()> {
         self.conn
             .call(|conn| {
@@ -35,6 +219,13 @@ impl Db {
                         message_id INTEGER,
                         file_id TEXT,
                         caption TEXT,
+                        translation TEXT,
+                        translation_lang TEXT,
+                        title TEXT,
+                        description TEXT,
+                        rating INTEGER,
+                        pinned INTEGER NOT NULL DEFAULT 0,
+                        excluded INTEGER NOT NULL DEFAULT 0,
                         created_at INTEGER NOT NULL DEFAULT (strftime('%s','now'))
                     );
                     CREATE TABLE IF NOT EXISTS files (
@@ -42,8 +233,79 @@ impl Db {
                         path TEXT,
                         created_at INTEGER NOT NULL DEFAULT (strftime('%s','now'))
                     );
+                    CREATE TABLE IF NOT EXISTS drafts (
+                        id INTEGER PRIMARY KEY AUTOINCREMENT,
+                        chat_id INTEGER NOT NULL,
+                        channel_id INTEGER NOT NULL,
+                        file_id TEXT,
+                        path TEXT,
+                        hash TEXT,
+                        caption TEXT,
+                        persona TEXT,
+                        status TEXT NOT NULL,
+                        prompt_message_id INTEGER,
+                        scheduled INTEGER NOT NULL DEFAULT 0,
+                        uploader_note TEXT,
+                        created_at INTEGER NOT NULL DEFAULT (strftime('%s','now'))
+                    );
+                    CREATE TABLE IF NOT EXISTS caption_variants (
+                        id INTEGER PRIMARY KEY AUTOINCREMENT,
+                        draft_id INTEGER NOT NULL,
+                        batch INTEGER NOT NULL,
+                        position INTEGER NOT NULL,
+                        caption TEXT NOT NULL,
+                        persona TEXT,
+                        chosen INTEGER NOT NULL DEFAULT 0,
+                        created_at INTEGER NOT NULL DEFAULT (strftime('%s','now'))
+                    );
+                    CREATE TABLE IF NOT EXISTS usage (
+                        id INTEGER PRIMARY KEY AUTOINCREMENT,
+                        model TEXT NOT NULL,
+                        prompt_tokens INTEGER NOT NULL,
+                        completion_tokens INTEGER NOT NULL,
+                        latency_ms INTEGER NOT NULL,
+                        cost_usd REAL NOT NULL,
+                        created_at INTEGER NOT NULL DEFAULT (strftime('%s','now'))
+                    );
+                    CREATE TABLE IF NOT EXISTS caption_cache (
+                        image_hash TEXT NOT NULL,
+                        prompt_hash TEXT NOT NULL,
+                        model TEXT NOT NULL,
+                        captions TEXT NOT NULL,
+                        created_at INTEGER NOT NULL DEFAULT (strftime('%s','now')),
+                        PRIMARY KEY (image_hash, prompt_hash, model)
+                    );
+                    CREATE TABLE IF NOT EXISTS caption_meta (
+                        caption_hash TEXT PRIMARY KEY,
+                        title TEXT NOT NULL,
+                        description TEXT NOT NULL,
+                        price_min REAL,
+                        price_max REAL,
+                        currency TEXT,
+                        created_at INTEGER NOT NULL DEFAULT (strftime('%s','now'))
+                    );
+                    CREATE TABLE IF NOT EXISTS critiques (
+                        id INTEGER PRIMARY KEY AUTOINCREMENT,
+                        original_hash TEXT NOT NULL,
+                        score INTEGER NOT NULL,
+                        reason TEXT NOT NULL,
+                        original TEXT NOT NULL,
+                        rewritten TEXT,
+                        model TEXT NOT NULL,
+                        created_at INTEGER NOT NULL DEFAULT (strftime('%s','now'))
+                    );
+                    CREATE INDEX IF NOT EXISTS critiques_original ON critiques(original_hash);
                     "#,
                 )?;
+                ensure_column(conn, "drafts", "persona", "TEXT")?;
+                ensure_column(conn, "drafts", "uploader_note", "TEXT")?;
+                ensure_column(conn, "posts", "translation", "TEXT")?;
+                ensure_column(conn, "posts", "translation_lang", "TEXT")?;
+                ensure_column(conn, "posts", "title", "TEXT")?;
+                ensure_column(conn, "posts", "description", "TEXT")?;
+                ensure_column(conn, "posts", "rating", "INTEGER")?;
+                ensure_column(conn, "posts", "pinned", "INTEGER NOT NULL DEFAULT 0")?;
+                ensure_column(conn, "posts", "excluded", "INTEGER NOT NULL DEFAULT 0")?;
                 Ok(())
             })
             .await?;
@@ -88,13 +350,57 @@ impl Db {
         Ok(())
     }
 
-/// Добавляет запись о публикации в таблицу `posts` (для аудита и отладки).
-    pub async fn log_post(&self, channel_id: i64, message_id: Option<i64>, file_id: Option<String>, caption: Option<String>) -> Result<()> {
+/// Возвращает персону, выбранную командой /persona (ключ `persona` в `config`).
+    pub async fn get_persona(&self) -> Result<Option<String>> {
+        let val = self
+            .conn
+            .call(|conn| {
+                Ok(conn
+                    .query_row("SELECT value FROM config WHERE key = 'persona'", [], |row| {
+                        row.get::<_, String>(0)
+                    })
+                    .optional()?)
+            })
+            .await?;
+        Ok(val)
+    }
+
+/// Сохраняет персону, выбранную командой /persona; `None` — сбросить выбор.
+    pub async fn set_persona(&self, name: Option<String>) -> Result<()> {
+        self.conn
+            .call(move |conn| {
+                match name {
+                    Some(name) => conn.execute(
+                        "INSERT INTO config(key, value) VALUES('persona', ?1) \
+                         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                        [name],
+                    )?,
+                    None => conn.execute("DELETE FROM config WHERE key = 'persona'", [])?,
+                };
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+/// Добавляет запись о публикации в таблицу `posts` (для аудита и каталога работ).
+    pub async fn log_post(&self, rec: PostRecord) -> Result<()> {
+        let (translation, translation_lang) = rec.translation.unzip();
         self.conn
             .call(move |conn| {
                 conn.execute(
-                    "INSERT INTO posts(channel_id, message_id, file_id, caption) VALUES(?1, ?2, ?3, ?4)",
-                    rusqlite::params![channel_id, message_id, file_id, caption],
+                    "INSERT INTO posts(channel_id, message_id, file_id, caption, translation, translation_lang, title, description) \
+                     VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    rusqlite::params![
+                        rec.channel_id,
+                        rec.message_id,
+                        rec.file_id,
+                        rec.caption,
+                        translation,
+                        translation_lang,
+                        rec.title,
+                        rec.description
+                    ],
                 )?;
                 Ok(())
             })
@@ -133,4 +439,514 @@ impl Db {
             .await?;
         Ok(())
     }
+
+/// Создаёт черновик и возвращает его `id`.
+    pub async fn create_draft(&self, draft: NewDraft) -> Result<i64> {
+        let id = self
+            .conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO drafts(chat_id, channel_id, file_id, path, hash, status, scheduled, uploader_note) \
+                     VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    rusqlite::params![
+                        draft.chat_id,
+                        draft.channel_id,
+                        draft.file_id,
+                        draft.path,
+                        draft.hash,
+                        draft.status.as_str(),
+                        draft.scheduled,
+                        draft.uploader_note
+                    ],
+                )?;
+                Ok(conn.last_insert_rowid())
+            })
+            .await?;
+        Ok(id)
+    }
+
+/// Читает черновик по `id`.
+    pub async fn get_draft(&self, id: i64) -> Result<Option<Draft>> {
+        let draft = self
+            .conn
+            .call(move |conn| {
+                let sql = format!("SELECT {} FROM drafts WHERE id = ?1", DRAFT_COLUMNS);
+                Ok(conn.query_row(&sql, [id], draft_from_row).optional()?)
+            })
+            .await?;
+        Ok(draft)
+    }
+
+/// Ищет черновик, ожидающий текста (новой или исправленной подписи), по сообщению
+/// бота, на которое ответил пользователь.
+    pub async fn find_draft_by_prompt(&self, chat_id: i64, message_id: i32) -> Result<Option<Draft>> {
+        let draft = self
+            .conn
+            .call(move |conn| {
+                let sql = format!(
+                    "SELECT {} FROM drafts WHERE chat_id = ?1 AND prompt_message_id = ?2 \
+                     AND status IN (?3, ?4) ORDER BY id DESC LIMIT 1",
+                    DRAFT_COLUMNS
+                );
+                Ok(conn
+                    .query_row(
+                        &sql,
+                        rusqlite::params![
+                            chat_id,
+                            message_id,
+                            DraftStatus::AwaitingText.as_str(),
+                            DraftStatus::Editing.as_str()
+                        ],
+                        draft_from_row,
+                    )
+                    .optional()?)
+            })
+            .await?;
+        Ok(draft)
+    }
+
+/// Все черновики в состоянии `status`, от старых к новым.
+    pub async fn list_drafts(&self, status: DraftStatus) -> Result<Vec<Draft>> {
+        let drafts = self
+            .conn
+            .call(move |conn| {
+                let sql = format!(
+                    "SELECT {} FROM drafts WHERE status = ?1 ORDER BY id",
+                    DRAFT_COLUMNS
+                );
+                let mut stmt = conn.prepare(&sql)?;
+                let rows = stmt.query_map([status.as_str()], draft_from_row)?;
+                Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+            })
+            .await?;
+        Ok(drafts)
+    }
+
+/// Есть ли незавершённый черновик для файла с хэшем `hash`.
+    pub async fn has_open_draft_for_hash(&self, hash: &str) -> Result<bool> {
+        let h = hash.to_string();
+        let exists = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT 1 FROM drafts WHERE hash = ?1 \
+                     AND status NOT IN ('published', 'cancelled') LIMIT 1",
+                )?;
+                let mut rows = stmt.query([h])?;
+                Ok(rows.next()?.is_some())
+            })
+            .await?;
+        Ok(exists)
+    }
+
+/// Запоминает сообщение бота, ответом на которое придёт текст подписи.
+    pub async fn set_draft_prompt(&self, id: i64, message_id: i32) -> Result<()> {
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "UPDATE drafts SET prompt_message_id = ?2 WHERE id = ?1",
+                    rusqlite::params![id, message_id],
+                )?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+/// Сохраняет текущий вариант подписи черновика.
+    pub async fn set_draft_caption(&self, id: i64, caption: &str) -> Result<()> {
+        let caption = caption.to_string();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "UPDATE drafts SET caption = ?2 WHERE id = ?1",
+                    rusqlite::params![id, caption],
+                )?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+/// Запоминает персону, выбранную для черновика.
+    pub async fn set_draft_persona(&self, id: i64, persona: &str) -> Result<()> {
+        let persona = persona.to_string();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "UPDATE drafts SET persona = ?2 WHERE id = ?1",
+                    rusqlite::params![id, persona],
+                )?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+/// Обновляет состояние черновика.
+    pub async fn set_draft_status(&self, id: i64, status: DraftStatus) -> Result<()> {
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "UPDATE drafts SET status = ?2 WHERE id = ?1",
+                    rusqlite::params![id, status.as_str()],
+                )?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+/// Отменяет все незавершённые черновики чата; возвращает их количество.
+    pub async fn cancel_open_drafts(&self, chat_id: i64) -> Result<usize> {
+        let n = self
+            .conn
+            .call(move |conn| {
+                Ok(conn.execute(
+                    "UPDATE drafts SET status = ?2 WHERE chat_id = ?1 \
+                     AND status NOT IN ('published', 'cancelled')",
+                    rusqlite::params![chat_id, DraftStatus::Cancelled.as_str()],
+                )?)
+            })
+            .await?;
+        Ok(n)
+    }
+
+/// Сохраняет очередной набор вариантов подписи для черновика (номера с 1).
+    pub async fn add_caption_variants(&self, draft_id: i64, persona: Option<String>, variants: Vec<String>) -> Result<()> {
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let batch: i64 = tx.query_row(
+                    "SELECT COALESCE(MAX(batch), 0) + 1 FROM caption_variants WHERE draft_id = ?1",
+                    [draft_id],
+                    |row| row.get(0),
+                )?;
+                for (i, caption) in variants.iter().enumerate() {
+                    tx.execute(
+                        "INSERT INTO caption_variants(draft_id, batch, position, caption, persona) \
+                         VALUES(?1, ?2, ?3, ?4, ?5)",
+                        rusqlite::params![draft_id, batch, i as i64 + 1, caption, persona],
+                    )?;
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+/// Варианты подписи, из которых ещё не выбран ни один (последний набор черновика).
+    pub async fn pending_caption_variants(&self, draft_id: i64) -> Result<Vec<String>> {
+        let variants = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT caption FROM caption_variants v WHERE draft_id = ?1 AND batch = \
+                     (SELECT MAX(batch) FROM caption_variants WHERE draft_id = ?1) \
+                     AND NOT EXISTS (SELECT 1 FROM caption_variants c WHERE c.draft_id = v.draft_id \
+                     AND c.batch = v.batch AND c.chosen = 1) ORDER BY position",
+                )?;
+                let rows = stmt.query_map([draft_id], |row| row.get::<_, String>(0))?;
+                Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+            })
+            .await?;
+        Ok(variants)
+    }
+
+/// Отмечает выбранный вариант из последнего набора и возвращает его текст.
+    pub async fn choose_caption_variant(&self, draft_id: i64, position: usize) -> Result<Option<String>> {
+        let caption = self
+            .conn
+            .call(move |conn| {
+                let batch = "(SELECT MAX(batch) FROM caption_variants WHERE draft_id = ?1)";
+                let caption = conn
+                    .query_row(
+                        &format!(
+                            "SELECT caption FROM caption_variants WHERE draft_id = ?1 \
+                             AND batch = {} AND position = ?2",
+                            batch
+                        ),
+                        rusqlite::params![draft_id, position as i64],
+                        |row| row.get::<_, String>(0),
+                    )
+                    .optional()?;
+                if caption.is_some() {
+                    conn.execute(
+                        &format!(
+                            "UPDATE caption_variants SET chosen = 1 WHERE draft_id = ?1 \
+                             AND batch = {} AND position = ?2",
+                            batch
+                        ),
+                        rusqlite::params![draft_id, position as i64],
+                    )?;
+                }
+                Ok(caption)
+            })
+            .await?;
+        Ok(caption)
+    }
+
+/// Подписи из кэша для изображения, промпта и модели.
+    pub async fn get_cached_captions(&self, image_hash: &str, prompt_hash: &str, model: &str) -> Result<Option<Vec<String>>> {
+        let key = (image_hash.to_string(), prompt_hash.to_string(), model.to_string());
+        let raw = self
+            .conn
+            .call(move |conn| {
+                Ok(conn
+                    .query_row(
+                        "SELECT captions FROM caption_cache \
+                         WHERE image_hash = ?1 AND prompt_hash = ?2 AND model = ?3",
+                        rusqlite::params![key.0, key.1, key.2],
+                        |row| row.get::<_, String>(0),
+                    )
+                    .optional()?)
+            })
+            .await?;
+        Ok(raw.and_then(|r| serde_json::from_str(&r).ok()))
+    }
+
+/// Сохраняет (или заменяет) подписи в кэше.
+    pub async fn put_cached_captions(&self, image_hash: &str, prompt_hash: &str, model: &str, captions: &[String]) -> Result<()> {
+        let key = (image_hash.to_string(), prompt_hash.to_string(), model.to_string());
+        let raw = serde_json::to_string(captions)?;
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO caption_cache(image_hash, prompt_hash, model, captions) \
+                     VALUES(?1, ?2, ?3, ?4) ON CONFLICT(image_hash, prompt_hash, model) \
+                     DO UPDATE SET captions = excluded.captions, created_at = strftime('%s','now')",
+                    rusqlite::params![key.0, key.1, key.2, raw],
+                )?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+/// Сохраняет (или заменяет) поля структурированного ответа для подписи.
+    pub async fn put_caption_meta(&self, caption_hash: &str, meta: CaptionMeta) -> Result<()> {
+        let hash = caption_hash.to_string();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO caption_meta(caption_hash, title, description, price_min, price_max, currency) \
+                     VALUES(?1, ?2, ?3, ?4, ?5, ?6) ON CONFLICT(caption_hash) DO UPDATE SET \
+                     title = excluded.title, description = excluded.description, price_min = excluded.price_min, \
+                     price_max = excluded.price_max, currency = excluded.currency, created_at = strftime('%s','now')",
+                    rusqlite::params![hash, meta.title, meta.description, meta.price_min, meta.price_max, meta.currency],
+                )?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+/// Поля структурированного ответа для подписи с хэшем `caption_hash`.
+    pub async fn get_caption_meta(&self, caption_hash: &str) -> Result<Option<CaptionMeta>> {
+        let hash = caption_hash.to_string();
+        let meta = self
+            .conn
+            .call(move |conn| {
+                Ok(conn
+                    .query_row(
+                        "SELECT title, description, price_min, price_max, currency FROM caption_meta \
+                         WHERE caption_hash = ?1",
+                        [hash],
+                        |row| {
+                            Ok(CaptionMeta {
+                                title: row.get(0)?,
+                                description: row.get(1)?,
+                                price_min: row.get(2)?,
+                                price_max: row.get(3)?,
+                                currency: row.get(4)?,
+                            })
+                        },
+                    )
+                    .optional()?)
+            })
+            .await?;
+        Ok(meta)
+    }
+
+/// Сохраняет оценку критика для подписи с хэшем `original_hash`.
+    pub async fn log_critique(&self, original_hash: &str, critique: Critique) -> Result<()> {
+        let hash = original_hash.to_string();
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO critiques(original_hash, score, reason, original, rewritten, model) \
+                     VALUES(?1, ?2, ?3, ?4, ?5, ?6)",
+                    rusqlite::params![
+                        hash,
+                        critique.score,
+                        critique.reason,
+                        critique.original,
+                        critique.rewritten,
+                        critique.model
+                    ],
+                )?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+/// Последняя оценка критика для подписи с хэшем `original_hash`.
+    pub async fn last_critique(&self, original_hash: &str) -> Result<Option<Critique>> {
+        let hash = original_hash.to_string();
+        let critique = self
+            .conn
+            .call(move |conn| {
+                Ok(conn
+                    .query_row(
+                        "SELECT score, reason, original, rewritten, model FROM critiques \
+                         WHERE original_hash = ?1 ORDER BY id DESC LIMIT 1",
+                        [hash],
+                        |row| {
+                            Ok(Critique {
+                                score: row.get(0)?,
+                                reason: row.get(1)?,
+                                original: row.get(2)?,
+                                rewritten: row.get(3)?,
+                                model: row.get(4)?,
+                            })
+                        },
+                    )
+                    .optional()?)
+            })
+            .await?;
+        Ok(critique)
+    }
+
+/// Записывает расход одной генерации.
+    pub async fn record_usage(&self, rec: UsageRecord) -> Result<()> {
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO usage(model, prompt_tokens, completion_tokens, latency_ms, cost_usd) \
+                     VALUES(?1, ?2, ?3, ?4, ?5)",
+                    rusqlite::params![
+                        rec.model,
+                        rec.prompt_tokens as i64,
+                        rec.completion_tokens as i64,
+                        rec.latency_ms as i64,
+                        rec.cost_usd
+                    ],
+                )?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+/// Суммарный расход начиная с момента `since` (unix-время).
+    pub async fn usage_totals(&self, since: i64) -> Result<UsageTotals> {
+        let totals = self
+            .conn
+            .call(move |conn| {
+                Ok(conn.query_row(
+                    "SELECT COUNT(*), COALESCE(SUM(prompt_tokens), 0), \
+                     COALESCE(SUM(completion_tokens), 0), COALESCE(SUM(cost_usd), 0.0) \
+                     FROM usage WHERE created_at >= ?1",
+                    [since],
+                    |row| {
+                        Ok(UsageTotals {
+                            requests: row.get::<_, i64>(0)? as u64,
+                            prompt_tokens: row.get::<_, i64>(1)? as u64,
+                            completion_tokens: row.get::<_, i64>(2)? as u64,
+                            cost_usd: row.get(3)?,
+                        })
+                    },
+                )?)
+            })
+            .await?;
+        Ok(totals)
+    }
+
+/// До `limit` подписей для примеров стиля: сначала закреплённые, затем самые
+/// новые (или, при `by_rating`, с наивысшей оценкой); исключённые не попадают.
+    pub async fn style_examples(&self, limit: usize, by_rating: bool) -> Result<Vec<StyleExample>> {
+        let order = if by_rating {
+            "pinned DESC, rating IS NULL, rating DESC, created_at DESC, id DESC"
+        } else {
+            "pinned DESC, created_at DESC, id DESC"
+        };
+        self.query_examples(format!("excluded = 0 ORDER BY {}", order), limit)
+            .await
+    }
+
+/// Последние публикации с подписью, включая исключённые из примеров (для /examples).
+    pub async fn recent_posts(&self, limit: usize) -> Result<Vec<StyleExample>> {
+        self.query_examples("1 ORDER BY created_at DESC, id DESC".to_string(), limit)
+            .await
+    }
+
+    async fn query_examples(&self, filter: String, limit: usize) -> Result<Vec<StyleExample>> {
+        let sql = format!(
+            "SELECT id, caption, rating, pinned, excluded FROM posts \
+             WHERE caption IS NOT NULL AND trim(caption) <> '' AND {} LIMIT ?1",
+            filter
+        );
+        let examples = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&sql)?;
+                let rows = stmt
+                    .query_map([limit as i64], |row| {
+                        Ok(StyleExample {
+                            id: row.get(0)?,
+                            caption: row.get(1)?,
+                            rating: row.get(2)?,
+                            pinned: row.get::<_, i64>(3)? != 0,
+                            excluded: row.get::<_, i64>(4)? != 0,
+                        })
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok(rows)
+            })
+            .await?;
+        Ok(examples)
+    }
+
+/// Закрепляет публикацию в примерах стиля (или снимает закрепление).
+/// Закрепление снимает исключение; снятие закрепления его не трогает.
+/// Возвращает `false`, если публикации с таким id нет.
+    pub async fn set_post_pinned(&self, id: i64, pinned: bool) -> Result<bool> {
+        self.update_post(
+            id,
+            "pinned = ?2, excluded = CASE WHEN ?2 THEN 0 ELSE excluded END",
+            pinned as i64,
+        )
+        .await
+    }
+
+/// Исключает публикацию из примеров стиля (или возвращает её в пул).
+/// Исключение снимает закрепление; возврат в пул его не трогает.
+    pub async fn set_post_excluded(&self, id: i64, excluded: bool) -> Result<bool> {
+        self.update_post(
+            id,
+            "excluded = ?2, pinned = CASE WHEN ?2 THEN 0 ELSE pinned END",
+            excluded as i64,
+        )
+        .await
+    }
+
+/// Оценка публикации от 1 до 5 (для выбора примеров по оценке).
+    pub async fn set_post_rating(&self, id: i64, rating: i64) -> Result<bool> {
+        self.update_post(id, "rating = ?2", rating).await
+    }
+
+    async fn update_post(&self, id: i64, set: &'static str, value: i64) -> Result<bool> {
+        let n = self
+            .conn
+            .call(move |conn| {
+                Ok(conn.execute(
+                    &format!("UPDATE posts SET {} WHERE id = ?1", set),
+                    rusqlite::params![id, value],
+                )?)
+            })
+            .await?;
+        Ok(n > 0)
+    }
 }
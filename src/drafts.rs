@@ -0,0 +1,724 @@
+// Черновики постов: запрос подписи у администратора, приём ответа текстом,
+// утверждение подписи кнопками под предпросмотром и фоновые повторы генерации
+// для отложенных публикаций.
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Result;
+use teloxide::prelude::*;
+use teloxide::types::{
+    CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, MessageId,
+    ReplyParameters,
+};
+use tokio::time::{interval, Duration};
+
+use crate::caption::MESSAGE_LIMIT;
+use crate::captioning::{check_policy, generate_variants, sha256_hex, CaptionJob, UploaderNote};
+use crate::config::Config;
+use crate::db::{Db, Draft, DraftStatus, NewDraft};
+use crate::format::Formatted;
+use crate::logging::{log, Level};
+use crate::policy::{self, Violation};
+use crate::prompts::{active_persona, list_personas};
+use crate::provider::CaptionProvider;
+use crate::publish::{load_photo, notify_published, publish_photo, PhotoSource, Post};
+use crate::sidecar::{load_sidecar, Sidecar};
+use crate::translate::translate_for;
+
+const ASK_TEXT: &str =
+    "Не удалось сгенерировать подпись. Ответьте на это сообщение текстом подписи — \
+опубликую пост с ним. /cancel — отменить.";
+
+const EDIT_TEXT: &str = "Пришлите исправленный текст подписи ответом на это сообщение.";
+
+/// Максимальная длина `callback_data` в Telegram (байт).
+const CALLBACK_DATA_LIMIT: usize = 64;
+
+/// Действие кнопки под предпросмотром (`draft:<id>:<действие>[:<персона>]`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DraftAction {
+    Publish,
+    Regenerate,
+    Edit,
+    /// Показать список персон.
+    Personas,
+    /// Перегенерировать подпись с выбранной персоной.
+    Persona(String),
+    /// Выбрать вариант подписи (номер с 1).
+    Pick(usize),
+    /// Вернуться к основным кнопкам.
+    Back,
+    Cancel,
+}
+
+impl DraftAction {
+    fn data(&self, id: i64) -> String {
+        match self {
+            DraftAction::Publish => format!("draft:{}:publish", id),
+            DraftAction::Regenerate => format!("draft:{}:regen", id),
+            DraftAction::Edit => format!("draft:{}:edit", id),
+            DraftAction::Personas => format!("draft:{}:persona", id),
+            DraftAction::Persona(name) => format!("draft:{}:persona:{}", id, name),
+            DraftAction::Pick(n) => format!("draft:{}:pick:{}", id, n),
+            DraftAction::Back => format!("draft:{}:back", id),
+            DraftAction::Cancel => format!("draft:{}:cancel", id),
+        }
+    }
+
+    fn parse(data: &str) -> Option<(i64, Self)> {
+        let mut parts = data.splitn(4, ':');
+        if parts.next()? != "draft" {
+            return None;
+        }
+        let id = parts.next()?.parse().ok()?;
+        let action = match (parts.next()?, parts.next()) {
+            ("publish", None) => DraftAction::Publish,
+            ("regen", None) => DraftAction::Regenerate,
+            ("edit", None) => DraftAction::Edit,
+            ("persona", None) => DraftAction::Personas,
+            ("persona", Some(name)) => DraftAction::Persona(name.to_string()),
+            ("pick", Some(n)) => DraftAction::Pick(n.parse().ok()?),
+            ("back", None) => DraftAction::Back,
+            ("cancel", None) => DraftAction::Cancel,
+            _ => return None,
+        };
+        Some((id, action))
+    }
+}
+
+fn main_keyboard(id: i64) -> InlineKeyboardMarkup {
+    let button =
+        |text: &str, action: DraftAction| InlineKeyboardButton::callback(text, action.data(id));
+    InlineKeyboardMarkup::new(vec![
+        vec![
+            button("✅ Опубликовать", DraftAction::Publish),
+            button("🔄 Заново", DraftAction::Regenerate),
+        ],
+        vec![
+            button("✏️ Изменить текст", DraftAction::Edit),
+            button("🎭 Персона", DraftAction::Personas),
+        ],
+        vec![button("✖️ Отменить", DraftAction::Cancel)],
+    ])
+}
+
+fn variants_keyboard(id: i64, count: usize) -> InlineKeyboardMarkup {
+    let numbers = (1..=count)
+        .map(|n| InlineKeyboardButton::callback(n.to_string(), DraftAction::Pick(n).data(id)))
+        .collect::<Vec<_>>();
+    InlineKeyboardMarkup::new(vec![
+        numbers,
+        vec![
+            InlineKeyboardButton::callback("🔄 Заново", DraftAction::Regenerate.data(id)),
+            InlineKeyboardButton::callback("🎭 Персона", DraftAction::Personas.data(id)),
+        ],
+        vec![InlineKeyboardButton::callback(
+            "✖️ Отменить",
+            DraftAction::Cancel.data(id),
+        )],
+    ])
+}
+
+fn persona_keyboard(id: i64, personas: &[String]) -> InlineKeyboardMarkup {
+    let mut rows: Vec<Vec<InlineKeyboardButton>> = personas
+        .iter()
+        .map(|name| (name, DraftAction::Persona(name.clone()).data(id)))
+        .filter(|(_, data)| data.len() <= CALLBACK_DATA_LIMIT)
+        .map(|(name, data)| vec![InlineKeyboardButton::callback(name.clone(), data)])
+        .collect();
+    rows.push(vec![InlineKeyboardButton::callback(
+        "← Назад",
+        DraftAction::Back.data(id),
+    )]);
+    InlineKeyboardMarkup::new(rows)
+}
+
+impl Draft {
+    /// Источник фото черновика.
+    pub fn source(&self) -> Option<PhotoSource> {
+        match (&self.path, &self.file_id) {
+            (Some(path), _) => Some(PhotoSource::Path(PathBuf::from(path))),
+            (None, Some(id)) => Some(PhotoSource::FileId(id.clone())),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Создаёт черновик и просит администратора прислать подпись ответом.
+/// Для загрузки из чата вопрос задаётся ответом на исходное фото, для
+/// публикации из папки — фото отправляется в `chat_id` вместе с вопросом.
+pub async fn ask_for_caption(
+    bot: &Bot,
+    db: &Db,
+    mut draft: NewDraft,
+    reply_to: Option<teloxide::types::MessageId>,
+) -> Result<()> {
+    draft.status = DraftStatus::AwaitingText;
+    let chat_id = ChatId(draft.chat_id);
+    let path = draft.path.clone();
+    let id = db.create_draft(draft).await?;
+    let prompt = match (reply_to, path) {
+        (Some(reply_to), _) => {
+            bot.send_message(chat_id, ASK_TEXT)
+                .reply_parameters(ReplyParameters::new(reply_to))
+                .await?
+        }
+        (None, Some(path)) => {
+            bot.send_photo(chat_id, InputFile::file(PathBuf::from(path)))
+                .caption(ASK_TEXT)
+                .await?
+        }
+        (None, None) => bot.send_message(chat_id, ASK_TEXT).await?,
+    };
+    db.set_draft_prompt(id, prompt.id.0).await?;
+    log(
+        "drafts",
+        "ask",
+        Level::Info,
+        "Запрошена подпись у администратора",
+    )
+    .data("draft_id", id.to_string())
+    .data("chat_id", chat_id.to_string())
+    .print();
+    Ok(())
+}
+
+/// Текст предпросмотра: подпись без разметки (как её увидит читатель) и персона.
+fn preview_text(caption: &str, persona: Option<&str>, violations: &[Violation]) -> String {
+    let mut header = format!(
+        "Предпросмотр подписи (персона: {}):\n\n",
+        persona.unwrap_or("по умолчанию")
+    );
+    if !violations.is_empty() {
+        header.insert_str(
+            0,
+            &format!("⚠️ Подпись не прошла проверку: {}.\n\n", policy::describe(violations)),
+        );
+    }
+    let (doc, _) = Formatted::parse_or_plain(caption);
+    let (head, _) = doc.split(MESSAGE_LIMIT - header.encode_utf16().count());
+    format!("{}{}", header, head.visible())
+}
+
+/// Текст выбора из нескольких вариантов: нумерованный список, каждый вариант
+/// укорочен так, чтобы список поместился в одно сообщение.
+fn variants_text(variants: &[String], persona: Option<&str>) -> String {
+    let header = format!(
+        "Варианты подписи (персона: {}). Выберите номер:",
+        persona.unwrap_or("по умолчанию")
+    );
+    let budget = ((MESSAGE_LIMIT - header.encode_utf16().count()) / variants.len().max(1))
+        .saturating_sub(8)
+        .max(1);
+    let mut out = header;
+    for (i, v) in variants.iter().enumerate() {
+        let (doc, _) = Formatted::parse_or_plain(v);
+        let (head, _) = doc.split(budget);
+        out.push_str(&format!("\n\n{}. {}", i + 1, head.visible()));
+    }
+    out
+}
+
+/// Подпись, которую художница прислала вместе с фото черновика.
+fn draft_note(draft: &Draft) -> Option<UploaderNote> {
+    draft.uploader_note.as_deref().and_then(UploaderNote::parse)
+}
+
+/// Персона, с которой генерируется подпись черновика.
+async fn draft_persona(db: &Db, config: &Config, draft: &Draft) -> Result<Option<String>> {
+    match &draft.persona {
+        Some(p) => Ok(Some(p.clone())),
+        None => active_persona(config, db, draft.channel_id, draft.scheduled).await,
+    }
+}
+
+/// Текст и кнопки предпросмотра: выбор варианта, если он ещё не сделан, иначе
+/// текущая подпись с основными кнопками.
+async fn preview(
+    db: &Db,
+    config: &Config,
+    draft: &Draft,
+) -> Result<(String, InlineKeyboardMarkup)> {
+    let persona = draft_persona(db, config, draft).await?;
+    let variants = db.pending_caption_variants(draft.id).await?;
+    if variants.len() > 1 {
+        return Ok((
+            variants_text(&variants, persona.as_deref()),
+            variants_keyboard(draft.id, variants.len()),
+        ));
+    }
+    let caption = draft.caption.as_deref().unwrap_or("");
+    // Текст с `!` в предпросмотре тоже проверяется: после перегенерации он лишь подсказка
+    let note = draft_note(draft).map(UploaderNote::into_hint);
+    Ok((
+        preview_text(
+            caption,
+            persona.as_deref(),
+            &check_policy(config, caption, note.as_ref())?,
+        ),
+        main_keyboard(draft.id),
+    ))
+}
+
+/// Отправляет предпросмотр подписи с кнопками и запоминает его сообщение.
+async fn send_preview(
+    bot: &Bot,
+    db: &Db,
+    config: &Config,
+    draft: &Draft,
+    reply_to: Option<MessageId>,
+) -> Result<()> {
+    let (text, keyboard) = preview(db, config, draft).await?;
+    let mut req = bot
+        .send_message(ChatId(draft.chat_id), text)
+        .reply_markup(keyboard);
+    if let Some(reply_to) = reply_to {
+        req = req.reply_parameters(ReplyParameters::new(reply_to));
+    }
+    let preview = req.await?;
+    db.set_draft_prompt(draft.id, preview.id.0).await?;
+    Ok(())
+}
+
+/// Обновляет сообщение предпросмотра после изменения черновика.
+async fn refresh_preview(
+    bot: &Bot,
+    db: &Db,
+    config: &Config,
+    id: i64,
+    chat_id: ChatId,
+    message_id: MessageId,
+) -> Result<()> {
+    let Some(draft) = db.get_draft(id).await? else {
+        return Ok(());
+    };
+    let (text, keyboard) = preview(db, config, &draft).await?;
+    bot.edit_message_text(chat_id, message_id, text)
+        .reply_markup(keyboard)
+        .await?;
+    Ok(())
+}
+
+/// Запоминает новые подписи черновика: первая становится текущей, а если их
+/// несколько — сохраняется набор вариантов для выбора.
+async fn offer_captions(
+    db: &Db,
+    config: &Config,
+    draft: &Draft,
+    captions: &[String],
+) -> Result<()> {
+    let Some(first) = captions.first() else {
+        anyhow::bail!("нет подписей для черновика {}", draft.id);
+    };
+    db.set_draft_caption(draft.id, first).await?;
+    if captions.len() > 1 {
+        let persona = draft_persona(db, config, draft).await?;
+        db.add_caption_variants(draft.id, persona, captions.to_vec())
+            .await?;
+    }
+    Ok(())
+}
+
+/// Создаёт черновик с готовой подписью (или несколькими вариантами) и отправляет
+/// его на утверждение. Для фото из чата предпросмотр приходит ответом на исходное
+/// сообщение, для публикации из папки — фото сначала отправляется в `chat_id`.
+pub async fn propose(
+    bot: &Bot,
+    db: &Db,
+    config: &Config,
+    mut draft: NewDraft,
+    captions: &[String],
+    reply_to: Option<MessageId>,
+) -> Result<()> {
+    draft.status = DraftStatus::Pending;
+    let path = draft.path.clone();
+    let chat_id = ChatId(draft.chat_id);
+    let id = db.create_draft(draft).await?;
+    let Some(draft) = db.get_draft(id).await? else {
+        anyhow::bail!("черновик {} не найден", id);
+    };
+    offer_captions(db, config, &draft, captions).await?;
+    let reply_to = match (reply_to, path) {
+        (Some(reply_to), _) => Some(reply_to),
+        (None, Some(path)) => Some(
+            bot.send_photo(chat_id, InputFile::file(PathBuf::from(path)))
+                .await?
+                .id,
+        ),
+        (None, None) => None,
+    };
+    send_preview(bot, db, config, &draft, reply_to).await?;
+    log(
+        "drafts",
+        "approval",
+        Level::Info,
+        "Подпись отправлена на утверждение",
+    )
+    .data("draft_id", id.to_string())
+    .data("chat_id", chat_id.to_string())
+    .print();
+    Ok(())
+}
+
+/// Откладывает публикацию: черновик будет обработан фоновыми повторами.
+pub async fn defer(db: &Db, mut draft: NewDraft) -> Result<i64> {
+    draft.status = DraftStatus::Deferred;
+    let id = db.create_draft(draft).await?;
+    log(
+        "drafts",
+        "defer",
+        Level::Info,
+        "Публикация отложена до успешной генерации",
+    )
+    .data("draft_id", id.to_string())
+    .print();
+    Ok(id)
+}
+
+/// Сопроводительные данные файла черновика (для фото из чата — пустые).
+async fn draft_sidecar(draft: &Draft) -> Sidecar {
+    match &draft.path {
+        Some(path) => load_sidecar(Path::new(path)).await,
+        None => Sidecar::default(),
+    }
+}
+
+/// Публикует черновик с подписью `caption` (с переводом по языку канала),
+/// отмечает файл опубликованным и сообщает об этом в чат черновика.
+pub async fn publish_draft(
+    bot: &Bot,
+    db: &Db,
+    config: &Config,
+    provider: &dyn CaptionProvider,
+    draft: &Draft,
+    caption: &str,
+) -> Result<()> {
+    let Some(source) = draft.source() else {
+        anyhow::bail!("у черновика {} нет фото", draft.id);
+    };
+    let bytes = load_photo(bot, config, &source).await?;
+    let translation = translate_for(config, db, provider, draft.channel_id, caption).await?;
+    let sidecar = draft_sidecar(draft).await;
+    let post = Post {
+        channel_id: draft.channel_id,
+        source: &source,
+        bytes: &bytes,
+        caption,
+        scheduled: draft.scheduled,
+        translation: translation.as_ref(),
+        sidecar: &sidecar,
+    };
+    let sent = publish_photo(bot, db, config, post).await?;
+    if let (Some(hash), Some(path)) = (&draft.hash, &draft.path) {
+        db.insert_file_hash(hash, path).await?;
+    }
+    db.set_draft_status(draft.id, DraftStatus::Published)
+        .await?;
+    notify_published(bot, ChatId(draft.chat_id), draft.channel_id, &sent).await?;
+    log("drafts", "publish", Level::Info, "Черновик опубликован")
+        .data("draft_id", draft.id.to_string())
+        .print();
+    Ok(())
+}
+
+/// Обработчик текстового ответа на сообщение бота: если это ответ на запрос
+/// подписи — публикуем черновик с присланным текстом.
+pub async fn handle_draft_reply(
+    bot: Bot,
+    msg: Message,
+    db: Arc<Db>,
+    config: Arc<Config>,
+    provider: Arc<dyn CaptionProvider>,
+) -> Result<()> {
+    let (Some(text), Some(reply)) = (msg.text(), msg.reply_to_message()) else {
+        return Ok(());
+    };
+    let Some(draft) = db.find_draft_by_prompt(msg.chat.id.0, reply.id.0).await? else {
+        return Ok(());
+    };
+    log("drafts", "reply", Level::Info, "Получен текст подписи")
+        .data("draft_id", draft.id.to_string())
+        .data("len", text.len().to_string())
+        .print();
+    if draft.status == DraftStatus::Editing {
+        // Исправленный текст снова показываем на утверждение; название и описание
+        // работы из структурированного ответа переходят к нему
+        if let Some(old) = &draft.caption {
+            if let Some(meta) = db.get_caption_meta(&sha256_hex(old.as_bytes())).await? {
+                db.put_caption_meta(&sha256_hex(text.trim().as_bytes()), meta)
+                    .await?;
+            }
+        }
+        db.set_draft_caption(draft.id, text.trim()).await?;
+        db.set_draft_status(draft.id, DraftStatus::Pending).await?;
+        let Some(draft) = db.get_draft(draft.id).await? else {
+            return Ok(());
+        };
+        return send_preview(&bot, &db, &config, &draft, Some(msg.id)).await;
+    }
+    publish_draft(&bot, &db, &config, provider.as_ref(), &draft, text.trim()).await
+}
+
+/// Обработчик кнопок под предпросмотром подписи.
+pub async fn handle_draft_callback(
+    bot: Bot,
+    q: CallbackQuery,
+    db: Arc<Db>,
+    config: Arc<Config>,
+    provider: Arc<dyn CaptionProvider>,
+) -> Result<()> {
+    let Some((id, action)) = q.data.as_deref().and_then(DraftAction::parse) else {
+        return Ok(());
+    };
+    let Some(message) = q.message.as_ref() else {
+        bot.answer_callback_query(q.id.clone()).await?;
+        return Ok(());
+    };
+    let (chat_id, message_id) = (message.chat().id, message.id());
+    log("drafts", "callback", Level::Info, "Нажата кнопка черновика")
+        .data("draft_id", id.to_string())
+        .data("action", format!("{:?}", action))
+        .data("from", q.from.id.0.to_string())
+        .print();
+    // Кнопки работают только под последним предпросмотром ожидающего черновика
+    let draft = db.get_draft(id).await?.filter(|d| {
+        d.chat_id == chat_id.0
+            && d.status == DraftStatus::Pending
+            && d.prompt_message_id == Some(message_id.0)
+    });
+    let Some(draft) = draft else {
+        bot.answer_callback_query(q.id.clone())
+            .text("Черновик уже обработан.")
+            .await?;
+        bot.edit_message_reply_markup(chat_id, message_id)
+            .await
+            .ok();
+        return Ok(());
+    };
+    match action {
+        DraftAction::Publish => {
+            bot.answer_callback_query(q.id.clone()).await?;
+            // Убираем кнопки сразу, чтобы повторное нажатие не опубликовало пост дважды
+            bot.edit_message_reply_markup(chat_id, message_id).await?;
+            let caption = draft.caption.clone().unwrap_or_default();
+            if let Err(err) =
+                publish_draft(&bot, &db, &config, provider.as_ref(), &draft, &caption).await
+            {
+                bot.edit_message_reply_markup(chat_id, message_id)
+                    .reply_markup(main_keyboard(id))
+                    .await
+                    .ok();
+                bot.send_message(chat_id, format!("Не удалось опубликовать: {}", err))
+                    .await?;
+                return Err(err);
+            }
+        }
+        DraftAction::Regenerate | DraftAction::Persona(_) => {
+            bot.answer_callback_query(q.id.clone())
+                .text("Генерирую новую подпись…")
+                .await?;
+            let mut draft = draft;
+            if let DraftAction::Persona(name) = &action {
+                if !list_personas(&config).contains(name) {
+                    bot.send_message(chat_id, format!("Персона «{}» не найдена.", name))
+                        .await?;
+                    return Ok(());
+                }
+                db.set_draft_persona(id, name).await?;
+                draft.persona = Some(name.clone());
+            }
+            // «Заново» всегда идёт к модели; смена персоны меняет промпт и ключ кэша
+            let fresh = action == DraftAction::Regenerate;
+            let captions =
+                match regenerate(&bot, &db, &config, provider.as_ref(), &draft, fresh).await {
+                    Ok(c) => c,
+                    Err(err) => {
+                        bot.send_message(
+                            chat_id,
+                            format!("Не удалось сгенерировать подпись: {}", err),
+                        )
+                        .await?;
+                        return Ok(());
+                    }
+                };
+            offer_captions(&db, &config, &draft, &captions).await?;
+            refresh_preview(&bot, &db, &config, id, chat_id, message_id).await?;
+        }
+        DraftAction::Pick(n) => {
+            let Some(caption) = db.choose_caption_variant(id, n).await? else {
+                bot.answer_callback_query(q.id.clone())
+                    .text("Такого варианта нет.")
+                    .await?;
+                return Ok(());
+            };
+            bot.answer_callback_query(q.id.clone()).await?;
+            db.set_draft_caption(id, &caption).await?;
+            log("drafts", "variants", Level::Info, "Выбран вариант подписи")
+                .data("draft_id", id.to_string())
+                .data("position", n.to_string())
+                .print();
+            refresh_preview(&bot, &db, &config, id, chat_id, message_id).await?;
+        }
+        DraftAction::Edit => {
+            bot.answer_callback_query(q.id.clone()).await?;
+            bot.edit_message_reply_markup(chat_id, message_id).await?;
+            let prompt = bot
+                .send_message(chat_id, EDIT_TEXT)
+                .reply_parameters(ReplyParameters::new(message_id))
+                .await?;
+            db.set_draft_prompt(id, prompt.id.0).await?;
+            db.set_draft_status(id, DraftStatus::Editing).await?;
+        }
+        DraftAction::Personas => {
+            let personas = list_personas(&config);
+            if personas.is_empty() {
+                bot.answer_callback_query(q.id.clone())
+                    .text("Персоны не настроены (prompts_dir).")
+                    .await?;
+                return Ok(());
+            }
+            bot.answer_callback_query(q.id.clone()).await?;
+            bot.edit_message_reply_markup(chat_id, message_id)
+                .reply_markup(persona_keyboard(id, &personas))
+                .await?;
+        }
+        DraftAction::Back => {
+            bot.answer_callback_query(q.id.clone()).await?;
+            let (_, keyboard) = preview(&db, &config, &draft).await?;
+            bot.edit_message_reply_markup(chat_id, message_id)
+                .reply_markup(keyboard)
+                .await?;
+        }
+        DraftAction::Cancel => {
+            db.set_draft_status(id, DraftStatus::Cancelled).await?;
+            bot.answer_callback_query(q.id.clone())
+                .text("Черновик отменён.")
+                .await?;
+            bot.edit_message_reply_markup(chat_id, message_id).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Фоновые повторы: раз в `caption_retry_secs` пытается сгенерировать подписи
+/// для отложенных черновиков и опубликовать их (или отправить на утверждение).
+pub async fn run_deferred_retry(
+    bot: Bot,
+    db: Arc<Db>,
+    config: Arc<Config>,
+    provider: Arc<dyn CaptionProvider>,
+) {
+    let mut ticker = interval(Duration::from_secs(config.caption_retry_secs.max(1)));
+    loop {
+        ticker.tick().await;
+        let drafts = match db.list_drafts(DraftStatus::Deferred).await {
+            Ok(d) => d,
+            Err(err) => {
+                log(
+                    "drafts",
+                    "retry",
+                    Level::Warn,
+                    "Не удалось прочитать черновики",
+                )
+                .data("error", err.to_string())
+                .print();
+                continue;
+            }
+        };
+        for draft in drafts {
+            if let Err(err) = retry_draft(&bot, &db, &config, provider.as_ref(), &draft).await {
+                log("drafts", "retry", Level::Warn, "Повтор генерации не удался")
+                    .data("draft_id", draft.id.to_string())
+                    .data("error", err.to_string())
+                    .print();
+            }
+        }
+    }
+}
+
+async fn retry_draft(
+    bot: &Bot,
+    db: &Db,
+    config: &Config,
+    provider: &dyn CaptionProvider,
+    draft: &Draft,
+) -> Result<()> {
+    if draft.source().is_none() {
+        db.set_draft_status(draft.id, DraftStatus::Cancelled)
+            .await?;
+        return Ok(());
+    }
+    let captions = regenerate(bot, db, config, provider, draft, false).await?;
+    // Подпись с нарушениями `caption_policy` публикуется только после утверждения
+    let note = draft_note(draft).map(UploaderNote::into_hint);
+    let violations = check_policy(config, &captions[0], note.as_ref())?;
+    if config.approval_required(draft.scheduled) || !violations.is_empty() {
+        offer_captions(db, config, draft, &captions).await?;
+        db.set_draft_status(draft.id, DraftStatus::Pending).await?;
+        let Some(draft) = db.get_draft(draft.id).await? else {
+            return Ok(());
+        };
+        return send_preview(bot, db, config, &draft, None).await;
+    }
+    publish_draft(bot, db, config, provider, draft, &captions[0]).await
+}
+
+/// Заново генерирует подпись для фото черновика (с учётом выбранной персоны);
+/// если подпись пойдёт на утверждение — `caption_variants` вариантов.
+/// `fresh` — не брать подпись из кэша.
+async fn regenerate(
+    bot: &Bot,
+    db: &Db,
+    config: &Config,
+    provider: &dyn CaptionProvider,
+    draft: &Draft,
+    fresh: bool,
+) -> Result<Vec<String>> {
+    let Some(source) = draft.source() else {
+        anyhow::bail!("у черновика {} нет фото", draft.id);
+    };
+    let bytes = load_photo(bot, config, &source).await?;
+    let sidecar = draft_sidecar(draft).await;
+    let job = CaptionJob {
+        image: &bytes,
+        image_hash: draft.hash.clone(),
+        channel_id: draft.channel_id,
+        scheduled: draft.scheduled,
+        sidecar,
+        persona: draft.persona.clone(),
+        fresh,
+        // Перегенерация всегда обращается к модели: текст с `!` становится подсказкой
+        note: draft_note(draft).map(UploaderNote::into_hint),
+    };
+    let n = if config.approval_required(draft.scheduled) {
+        config.caption_variants
+    } else {
+        1
+    };
+    generate_variants(config, db, provider, &job, n).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn callback_data_roundtrip() {
+        for action in [
+            DraftAction::Publish,
+            DraftAction::Regenerate,
+            DraftAction::Edit,
+            DraftAction::Personas,
+            DraftAction::Persona("nezhny_stil".to_string()),
+            DraftAction::Pick(3),
+            DraftAction::Back,
+            DraftAction::Cancel,
+        ] {
+            assert_eq!(DraftAction::parse(&action.data(42)), Some((42, action)));
+        }
+        assert_eq!(DraftAction::parse("draft:x:publish"), None);
+        assert_eq!(DraftAction::parse("other:1:publish"), None);
+        assert_eq!(DraftAction::parse("draft:1:publish:extra"), None);
+        assert_eq!(DraftAction::parse("draft:1:pick:x"), None);
+    }
+}
@@ -0,0 +1,209 @@
+// Память стиля: прошлые подписи из `posts` как примеры (few-shot) в системном
+// промпте, чтобы модель держалась голоса художницы. Пул примеров настраивается
+// командами /pin_example, /exclude_example и /rate.
+use anyhow::Result;
+
+use crate::config::{Config, ExamplesMode};
+use crate::db::{Db, StyleExample};
+use crate::logging::compact;
+
+/// Сколько символов примера попадает в промпт.
+const EXAMPLE_CHARS: usize = 700;
+
+/// Сколько последних публикаций показывает /examples помимо пула.
+const RECENT_POSTS: usize = 10;
+
+/// Примеры для промпта: до `style_examples` подписей по `style_examples_mode`.
+async fn pool(config: &Config, db: &Db) -> Result<Vec<StyleExample>> {
+    if config.style_examples == 0 {
+        return Ok(Vec::new());
+    }
+    db.style_examples(
+        config.style_examples,
+        config.style_examples_mode == ExamplesMode::Rated,
+    )
+    .await
+}
+
+/// Начало подписи не длиннее `EXAMPLE_CHARS` символов.
+fn excerpt(caption: &str) -> String {
+    let caption = caption.trim();
+    match caption.char_indices().nth(EXAMPLE_CHARS) {
+        Some((i, _)) => format!("{}…", &caption[..i]),
+        None => caption.to_string(),
+    }
+}
+
+/// Блок системного промпта с примерами; пустая строка, если примеров нет.
+pub async fn prompt_block(config: &Config, db: &Db) -> Result<String> {
+    let examples = pool(config, db).await?;
+    if examples.is_empty() {
+        return Ok(String::new());
+    }
+    let mut block = String::from(
+        "\n\nПримеры прошлых подписей художницы. Держись того же голоса и манеры, \
+         но не повторяй их текст:",
+    );
+    for (i, example) in examples.iter().enumerate() {
+        block.push_str(&format!(
+            "\n\n--- Пример {} ---\n{}",
+            i + 1,
+            excerpt(&example.caption)
+        ));
+    }
+    Ok(block)
+}
+
+/// Аргумент команды вида `<id>` или `<id> off`.
+fn parse_target(arg: &str) -> Option<(i64, bool)> {
+    let mut parts = arg.split_whitespace();
+    let id = parts.next()?.parse().ok()?;
+    let on = match parts.next() {
+        None => true,
+        Some("off") => false,
+        Some(_) => return None,
+    };
+    parts.next().is_none().then_some((id, on))
+}
+
+/// /pin_example <id> [off] — закрепить публикацию в примерах или снять закрепление.
+pub async fn pin(db: &Db, arg: &str) -> Result<String> {
+    let Some((id, on)) = parse_target(arg) else {
+        return Ok("Использование: /pin_example <id> или /pin_example <id> off".to_string());
+    };
+    Ok(match db.set_post_pinned(id, on).await? {
+        false => format!("Публикация {} не найдена. Список: /examples", id),
+        true if on => format!("Публикация {} закреплена в примерах стиля.", id),
+        true => format!("Публикация {} больше не закреплена.", id),
+    })
+}
+
+/// /exclude_example <id> [off] — исключить публикацию из примеров или вернуть её.
+pub async fn exclude(db: &Db, arg: &str) -> Result<String> {
+    let Some((id, on)) = parse_target(arg) else {
+        return Ok(
+            "Использование: /exclude_example <id> или /exclude_example <id> off".to_string(),
+        );
+    };
+    Ok(match db.set_post_excluded(id, on).await? {
+        false => format!("Публикация {} не найдена. Список: /examples", id),
+        true if on => format!("Публикация {} исключена из примеров стиля.", id),
+        true => format!("Публикация {} снова может быть примером.", id),
+    })
+}
+
+/// /rate <id> <1-5> — оценка публикации.
+pub async fn rate(db: &Db, arg: &str) -> Result<String> {
+    let parsed = match arg.split_whitespace().collect::<Vec<_>>()[..] {
+        [id, score] => id.parse::<i64>().ok().zip(score.parse::<i64>().ok()),
+        _ => None,
+    };
+    let Some((id, score)) = parsed.filter(|(_, s)| (1..=5).contains(s)) else {
+        return Ok("Использование: /rate <id> <оценка от 1 до 5>".to_string());
+    };
+    Ok(match db.set_post_rating(id, score).await? {
+        true => format!("Публикация {} оценена на {}.", id, score),
+        false => format!("Публикация {} не найдена. Список: /examples", id),
+    })
+}
+
+/// Строка списка: id публикации, отметки и начало подписи.
+fn list_entry(example: &StyleExample) -> String {
+    let mut marks = Vec::new();
+    if example.pinned {
+        marks.push("📌".to_string());
+    }
+    if example.excluded {
+        marks.push("🚫".to_string());
+    }
+    if let Some(r) = example.rating {
+        marks.push(format!("★{}", r));
+    }
+    format!(
+        "\n\n#{} {}\n{}",
+        example.id,
+        marks.join(" "),
+        compact(&example.caption, 120)
+    )
+}
+
+/// /examples — подписи, которые сейчас попадут в промпт, и последние публикации
+/// с их id для /pin_example, /exclude_example и /rate.
+pub async fn list(config: &Config, db: &Db) -> Result<String> {
+    let examples = pool(config, db).await?;
+    let mut text = if config.style_examples == 0 {
+        "Примеры стиля выключены: задайте style_examples в конфиге.".to_string()
+    } else if examples.is_empty() {
+        "Пока нет публикаций для примеров.".to_string()
+    } else {
+        String::from("Примеры стиля:")
+    };
+    for example in &examples {
+        text.push_str(&list_entry(example));
+    }
+    let recent: Vec<StyleExample> = db
+        .recent_posts(RECENT_POSTS)
+        .await?
+        .into_iter()
+        .filter(|p| !examples.iter().any(|e| e.id == p.id))
+        .collect();
+    if !recent.is_empty() {
+        text.push_str("\n\nПоследние публикации:");
+        for post in &recent {
+            text.push_str(&list_entry(post));
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::PostRecord;
+
+    #[test]
+    fn parses_command_targets() {
+        assert_eq!(parse_target("12"), Some((12, true)));
+        assert_eq!(parse_target(" 12  off "), Some((12, false)));
+        assert_eq!(parse_target("12 on please"), None);
+        assert_eq!(parse_target("abc"), None);
+    }
+
+    #[tokio::test]
+    async fn pin_and_exclude_clear_each_other_only_when_set() {
+        let db = Db::open(":memory:").await.unwrap();
+        db.log_post(PostRecord {
+            channel_id: 1,
+            message_id: Some(1),
+            file_id: None,
+            caption: Some("Пионы в утреннем свете.".to_string()),
+            translation: None,
+            title: None,
+            description: None,
+        })
+        .await
+        .unwrap();
+        let id = db.recent_posts(1).await.unwrap()[0].id;
+        let flags = || async {
+            let post = db.recent_posts(1).await.unwrap().remove(0);
+            (post.pinned, post.excluded)
+        };
+        db.set_post_excluded(id, true).await.unwrap();
+        db.set_post_pinned(id, false).await.unwrap();
+        assert_eq!(flags().await, (false, true));
+        db.set_post_pinned(id, true).await.unwrap();
+        assert_eq!(flags().await, (true, false));
+        db.set_post_excluded(id, false).await.unwrap();
+        assert_eq!(flags().await, (true, false));
+        db.set_post_excluded(id, true).await.unwrap();
+        assert_eq!(flags().await, (false, true));
+    }
+
+    #[test]
+    fn excerpt_is_cut_on_char_boundary() {
+        let long = "я".repeat(EXAMPLE_CHARS + 5);
+        let cut = excerpt(&long);
+        assert_eq!(cut.chars().count(), EXAMPLE_CHARS + 1);
+        assert!(cut.ends_with('…'));
+    }
+}
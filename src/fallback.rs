@@ -0,0 +1,233 @@
+// Запасные подписи без модели: шаблоны `fallback_templates` со слотами о
+// работе ({title}, {series}, {palette}, {date}, {weekday}, {season},
+// {signature}) и фразами, случайно выбранными из пулов `fallback_phrases`
+// для персоны. Используются, когда генерация не удалась или исчерпан бюджет.
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+use crate::config::Config;
+use crate::prompts::PromptVars;
+
+/// Шаблоны по умолчанию.
+const DEFAULT_TEMPLATES: &[&str] = &[
+    "**{title}**\n\n{opening}\n\n{story}\n\n{wish}\n{emoji}\n\n{signature}",
+    "**{title}**\n\n{story}\n\n{about}\n\n{wish}\n{emoji}\n\n{signature}",
+];
+
+/// Пулы фраз по умолчанию (ключ `fallback_phrases` для всех персон — `default`).
+const DEFAULT_PHRASES: &[(&str, &[&str])] = &[
+    (
+        "title",
+        &["Новая акварель", "Акварельный этюд", "Свежая работа из мастерской"],
+    ),
+    (
+        "opening",
+        &[
+            "Сегодня делюсь с вами новой работой.",
+            "Хочу показать вам то, над чем работала в последние дни.",
+            "За окном {season}, а у меня на столе — новая акварель.",
+        ],
+    ),
+    (
+        "story",
+        &[
+            "Палитра этой работы — {palette}. Я подбирала оттенки так, чтобы картина дышала светом.",
+            "Вода, бумага и немного терпения — и на листе появляется маленькая история.",
+            "Каждый мазок здесь — о тишине, свете и любви к простым вещам.",
+        ],
+    ),
+    (
+        "about",
+        &[
+            "Работа из серии «{series}».",
+            "Акварель на бумаге, написана от руки.",
+            "Работа существует в единственном экземпляре.",
+        ],
+    ),
+    (
+        "wish",
+        &[
+            "Пусть ваш день будет тёплым и светлым!",
+            "Желаю вам вдохновения и добрых встреч!",
+            "Пусть рядом всегда будет место для красоты и радости!",
+        ],
+    ),
+    ("emoji", &["🌸🎨✨", "🌿💧🎨", "☀️🌷💛"]),
+];
+
+static SLOT: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{([a-z_]+)\}").unwrap());
+
+/// Простой генератор псевдослучайных чисел (xorshift64*).
+struct Rng(u64);
+
+impl Rng {
+    fn pick<'a>(&mut self, items: &[&'a str]) -> Option<&'a str> {
+        if items.is_empty() {
+            return None;
+        }
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let n = self.0.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        Some(items[(n % items.len() as u64) as usize])
+    }
+}
+
+/// Зерно для выбора шаблона и фраз: изображение и текущее время, чтобы
+/// повторная запасная подпись отличалась.
+pub fn seed(image: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    image.hash(&mut hasher);
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Значение слота о работе; `None` — слот не из данных о работе.
+fn known(config: &Config, vars: &PromptVars, slot: &str) -> Option<String> {
+    match slot {
+        "title" | "series" | "palette" | "date" | "weekday" | "season" => {
+            Some(vars.render(&format!("{{{}}}", slot)))
+        }
+        "signature" => Some(config.signature.clone().unwrap_or_default()),
+        _ => None,
+    }
+}
+
+/// Фразы слота: пул персоны, затем `default` из конфига, затем встроенный.
+fn phrases<'a>(config: &'a Config, persona: Option<&str>, slot: &str) -> Vec<&'a str> {
+    let from_config = |key: &str| {
+        config
+            .fallback_phrases
+            .get(key)
+            .and_then(|pools| pools.get(slot))
+            .filter(|p| !p.is_empty())
+            .map(|p| p.iter().map(String::as_str).collect::<Vec<_>>())
+    };
+    persona
+        .and_then(from_config)
+        .or_else(|| from_config("default"))
+        .unwrap_or_else(|| {
+            DEFAULT_PHRASES
+                .iter()
+                .find(|(name, _)| *name == slot)
+                .map(|(_, p)| p.to_vec())
+                .unwrap_or_default()
+        })
+}
+
+/// Подставляет слоты о работе; `None`, если какой-то из них пуст.
+fn fill_known(config: &Config, vars: &PromptVars, phrase: &str) -> Option<String> {
+    let mut complete = true;
+    let text = SLOT.replace_all(phrase, |caps: &Captures| {
+        match known(config, vars, &caps[1]).filter(|v| !v.is_empty()) {
+            Some(value) => value,
+            None => {
+                complete = false;
+                String::new()
+            }
+        }
+    });
+    complete.then(|| text.into_owned())
+}
+
+/// Собирает запасную подпись. Пустой слот о работе (например, без названия)
+/// заменяется фразой из пула с тем же именем; фразы со слотами, для которых
+/// нет данных, не выбираются; пустые строки и абзацы убираются.
+pub fn render(config: &Config, persona: Option<&str>, vars: &PromptVars, seed: u64) -> String {
+    let mut rng = Rng(seed | 1);
+    let templates: Vec<&str> = if config.fallback_templates.is_empty() {
+        DEFAULT_TEMPLATES.to_vec()
+    } else {
+        config
+            .fallback_templates
+            .iter()
+            .map(String::as_str)
+            .collect()
+    };
+    let template = rng.pick(&templates).unwrap_or_default();
+    let text = SLOT.replace_all(template, |caps: &Captures| {
+        let slot = &caps[1];
+        if let Some(value) = known(config, vars, slot).filter(|v| !v.is_empty()) {
+            return value;
+        }
+        let candidates: Vec<String> = phrases(config, persona, slot)
+            .into_iter()
+            .filter_map(|p| fill_known(config, vars, p))
+            .collect();
+        let candidates: Vec<&str> = candidates.iter().map(String::as_str).collect();
+        rng.pick(&candidates).unwrap_or_default().to_string()
+    });
+    let mut out = String::new();
+    for block in text.split("\n\n") {
+        let block = block
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if block.is_empty() {
+            continue;
+        }
+        if !out.is_empty() {
+            out.push_str("\n\n");
+        }
+        out.push_str(&block);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config as config;
+    use time::macros::datetime;
+
+    fn vars(title: &str, palette: &[&str]) -> PromptVars {
+        PromptVars {
+            title: title.to_string(),
+            series: String::new(),
+            palette: palette.iter().map(|s| s.to_string()).collect(),
+            date: datetime!(2026-04-10 10:00 UTC),
+        }
+    }
+
+    #[test]
+    fn fills_slots_and_persona_pools() {
+        let config = config(serde_json::json!({
+            "signature": "— Аня",
+            "fallback_templates": ["**{title}**\n\n{story}\n\n{wish}\n\n{signature}"],
+            "fallback_phrases": {
+                "default": {"wish": ["Обнимаю!"]},
+                "gentle": {"story": ["Весь лист — {palette}, как {season}."]}
+            }
+        }));
+        let text = render(&config, Some("gentle"), &vars("Пионы", &["розовый"]), 7);
+        assert_eq!(
+            text,
+            "**Пионы**\n\nВесь лист — розовый, как весна.\n\nОбнимаю!\n\n— Аня"
+        );
+    }
+
+    #[test]
+    fn skips_phrases_without_data_and_empty_lines() {
+        let config = config(serde_json::json!({
+            "fallback_templates": ["**{title}**\n\n{story}\n\n{signature}"],
+            "fallback_phrases": {"default": {
+                "title": ["Новая акварель"],
+                "story": ["Палитра — {palette}.", "Просто история."]
+            }}
+        }));
+        for seed in 1..20 {
+            let text = render(&config, None, &vars("", &[]), seed);
+            assert_eq!(text, "**Новая акварель**\n\nПросто история.");
+        }
+    }
+}
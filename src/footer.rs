@@ -0,0 +1,152 @@
+// Подвал подписи (`caption_footer`, для канала — `channels.<id>.footer`):
+// контакты, строка о заказах, цена, постоянные хэштеги. Добавляется к каждой
+// подписи при публикации; его длина вычитается из лимита подписи, так что
+// обрезается текст модели, а не подвал.
+use std::collections::HashSet;
+
+use crate::caption::utf16_len;
+use crate::config::Config;
+use crate::format::Formatted;
+use crate::hashtags::is_hashtag;
+use crate::logging::{log, Level};
+use crate::sidecar::Sidecar;
+
+/// Разделитель между текстом подписи и подвалом.
+pub const FOOTER_SEPARATOR: &str = "\n\n";
+
+/// Подвал для канала: настройка канала (пустая строка — без подвала), иначе
+/// общий `caption_footer`. Слоты `{title}`, `{series}`, `{price}` берутся из
+/// сопроводительного файла, `{signature}` — из `signature`; строка со слотом,
+/// для которого нет данных, убирается целиком. Хэштеги, которые уже есть в
+/// тексте подписи `body`, из подвала убираются.
+pub fn footer_for(
+    config: &Config,
+    channel_id: i64,
+    sidecar: &Sidecar,
+    body: &str,
+) -> Option<String> {
+    let template = config
+        .channel(channel_id)
+        .footer
+        .or_else(|| config.caption_footer.clone())?;
+    let slots = [
+        ("{title}", sidecar.title.as_deref()),
+        ("{series}", sidecar.series.as_deref()),
+        ("{price}", sidecar.price.as_deref()),
+        ("{signature}", config.signature.as_deref()),
+    ];
+    let present: HashSet<String> = body
+        .split_whitespace()
+        .filter(|w| is_hashtag(w))
+        .map(str::to_lowercase)
+        .collect();
+    let repeated = |w: &str| is_hashtag(w) && present.contains(&w.to_lowercase());
+    let lines: Vec<String> = template
+        .lines()
+        .filter_map(|line| {
+            let mut line = line.to_string();
+            for (slot, value) in slots {
+                if line.contains(slot) {
+                    let value = value.map(str::trim).filter(|v| !v.is_empty())?;
+                    line = line.replace(slot, value);
+                }
+            }
+            if !line.split_whitespace().any(repeated) {
+                return Some(line);
+            }
+            let rest: Vec<&str> = line.split_whitespace().filter(|w| !repeated(w)).collect();
+            (!rest.is_empty()).then(|| rest.join(" "))
+        })
+        .collect();
+    let footer = lines.join("\n").trim().to_string();
+    (!footer.is_empty()).then_some(footer)
+}
+
+/// Делит подпись так, чтобы вместе с подвалом она уложилась в `limit`:
+/// текст режется по лимиту за вычетом подвала, подвал добавляется в конец
+/// первой части. Если подвал сам не помещается, подпись идёт без него.
+pub fn split_with_footer(
+    footer: Option<&Formatted>,
+    limit: usize,
+    split: impl FnOnce(usize) -> (Formatted, Option<Formatted>),
+) -> (Formatted, Option<Formatted>) {
+    let Some(footer) = footer.filter(|f| !f.is_empty()) else {
+        return split(limit);
+    };
+    let reserved = utf16_len(&footer.visible()) + utf16_len(FOOTER_SEPARATOR);
+    if reserved >= limit {
+        log(
+            "tg",
+            "publish",
+            Level::Warn,
+            "Подвал длиннее лимита подписи, публикуем без него",
+        )
+        .data("footer_len", reserved.to_string())
+        .print();
+        return split(limit);
+    }
+    let (head, tail) = split(limit - reserved);
+    (
+        Formatted::join(vec![head, footer.clone()], FOOTER_SEPARATOR),
+        tail,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::caption::CAPTION_LIMIT;
+    use crate::config::test_config as config;
+
+    #[test]
+    fn channel_footer_overrides_and_drops_lines_without_data() {
+        let config = config(serde_json::json!({
+            "signature": "— Анна",
+            "caption_footer": "{signature}\nЦена: {price}\nЗаказы открыты: @anna_art\n#акварель",
+            "channels": {"-100": {"footer": ""}}
+        }));
+        let sidecar = Sidecar {
+            price: Some("5000 ₽".to_string()),
+            ..Sidecar::default()
+        };
+        assert_eq!(
+            footer_for(&config, 1, &sidecar, "Пионы.").as_deref(),
+            Some("— Анна\nЦена: 5000 ₽\nЗаказы открыты: @anna_art\n#акварель")
+        );
+        assert_eq!(
+            footer_for(&config, 1, &Sidecar::default(), "Пионы.").as_deref(),
+            Some("— Анна\nЗаказы открыты: @anna_art\n#акварель")
+        );
+        assert_eq!(footer_for(&config, -100, &sidecar, "Пионы."), None);
+    }
+
+    #[test]
+    fn skips_footer_tags_already_in_caption() {
+        let config = config(serde_json::json!({
+            "caption_footer": "Заказы: @anna_art #заказ\n#Акварель #цветы"
+        }));
+        let body = "Пионы в саду.\n\n#акварель #заказ";
+        assert_eq!(
+            footer_for(&config, 1, &Sidecar::default(), body).as_deref(),
+            Some("Заказы: @anna_art\n#цветы")
+        );
+    }
+
+    #[test]
+    fn footer_length_is_reserved() {
+        let footer = Formatted::parse("[Заказать](https://t.me/anna) #акварель").unwrap();
+        let text = Formatted::plain(&"Очень длинная история о картине. ".repeat(60));
+        let (head, tail) =
+            split_with_footer(Some(&footer), CAPTION_LIMIT, |limit| text.split(limit));
+        let visible = head.visible();
+        assert!(utf16_len(&visible) <= CAPTION_LIMIT);
+        assert!(visible.ends_with("о картине.\n\nЗаказать #акварель"));
+        assert!(tail.is_some());
+        // Короткая подпись не режется
+        let short = Formatted::plain("Пионы.");
+        let (head, tail) =
+            split_with_footer(Some(&footer), CAPTION_LIMIT, |limit| short.split(limit));
+        assert_eq!(head.visible(), "Пионы.\n\nЗаказать #акварель");
+        assert_eq!(tail, None);
+    }
+}
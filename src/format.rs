@@ -0,0 +1,413 @@
+// Оформление подписей: разбор лёгкой разметки (**жирный**, *курсив*/_курсив_,
+// [текст](url)) и вывод в Telegram HTML или MarkdownV2 с экранированием.
+use serde::Deserialize;
+use teloxide::types::ParseMode;
+use thiserror::Error;
+
+use crate::caption::{cut_point, utf16_len};
+
+/// Формат подписи в Telegram.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptionFormat {
+    /// Обычный текст, разметка снимается.
+    #[default]
+    Plain,
+    Html,
+    #[serde(alias = "markdown")]
+    MarkdownV2,
+}
+
+impl CaptionFormat {
+    pub fn parse_mode(self) -> Option<ParseMode> {
+        match self {
+            CaptionFormat::Plain => None,
+            CaptionFormat::Html => Some(ParseMode::Html),
+            CaptionFormat::MarkdownV2 => Some(ParseMode::MarkdownV2),
+        }
+    }
+}
+
+/// Ошибка разбора разметки.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MarkupError {
+    #[error("незакрытое выделение «{0}»")]
+    Unclosed(&'static str),
+    #[error("вложенное выделение не поддерживается")]
+    Nested,
+    #[error("некорректная ссылка: {0}")]
+    BadLink(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Style {
+    Plain,
+    Bold,
+    Italic,
+    Link(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Span {
+    text: String,
+    style: Style,
+}
+
+/// Подпись, разобранная на фрагменты с оформлением.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Formatted {
+    spans: Vec<Span>,
+}
+
+impl Formatted {
+    /// Разбирает разметку; при ошибке возвращает её описание.
+    pub fn parse(src: &str) -> Result<Self, MarkupError> {
+        let chars: Vec<char> = src.trim().chars().collect();
+        let mut out = Formatted::default();
+        let mut buf = String::new();
+        let mut open: Option<(Style, &'static str)> = None;
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let prev = i.checked_sub(1).map(|p| chars[p]);
+            let next = chars.get(i + 1).copied();
+            if c == '\\' && next.is_some_and(|n| "*_[]()\\".contains(n)) {
+                buf.push(next.unwrap_or_default());
+                i += 2;
+                continue;
+            }
+            if c == '*' && next == Some('*') {
+                match &open {
+                    Some((Style::Bold, _)) => {
+                        out.push(std::mem::take(&mut buf), Style::Bold);
+                        open = None;
+                    }
+                    Some(_) => return Err(MarkupError::Nested),
+                    None => {
+                        out.push(std::mem::take(&mut buf), Style::Plain);
+                        open = Some((Style::Bold, "**"));
+                    }
+                }
+                i += 2;
+                continue;
+            }
+            if c == '*' || c == '_' {
+                let marker = if c == '*' { "*" } else { "_" };
+                let opening = prev.is_none_or(|p| !p.is_alphanumeric())
+                    && next.is_some_and(|n| !n.is_whitespace());
+                let closing = next.is_none_or(|n| !n.is_alphanumeric())
+                    && prev.is_some_and(|p| !p.is_whitespace());
+                match &open {
+                    Some((Style::Italic, m)) if *m == marker && closing => {
+                        out.push(std::mem::take(&mut buf), Style::Italic);
+                        open = None;
+                        i += 1;
+                        continue;
+                    }
+                    None if opening => {
+                        out.push(std::mem::take(&mut buf), Style::Plain);
+                        open = Some((Style::Italic, marker));
+                        i += 1;
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            if c == '[' {
+                if let Some((text, url, consumed)) = parse_link(&chars[i..])? {
+                    if open.is_some() {
+                        return Err(MarkupError::Nested);
+                    }
+                    out.push(std::mem::take(&mut buf), Style::Plain);
+                    out.push(text, Style::Link(url));
+                    i += consumed;
+                    continue;
+                }
+            }
+            buf.push(c);
+            i += 1;
+        }
+        if let Some((_, marker)) = open {
+            return Err(MarkupError::Unclosed(marker));
+        }
+        out.push(buf, Style::Plain);
+        Ok(out)
+    }
+
+    /// Текст без оформления: разметка не разбирается, маркеры `**` снимаются.
+    pub fn plain(src: &str) -> Self {
+        let mut out = Formatted::default();
+        out.push(src.trim().replace("**", ""), Style::Plain);
+        out
+    }
+
+    /// Разбирает разметку, а при ошибке откатывается к обычному тексту.
+    pub fn parse_or_plain(src: &str) -> (Self, Option<MarkupError>) {
+        match Self::parse(src) {
+            Ok(f) => (f, None),
+            Err(err) => (Self::plain(src), Some(err)),
+        }
+    }
+
+    fn push(&mut self, text: String, style: Style) {
+        if text.is_empty() {
+            return;
+        }
+        match self.spans.last_mut() {
+            Some(last) if last.style == style => last.text.push_str(&text),
+            _ => self.spans.push(Span { text, style }),
+        }
+    }
+
+    /// Видимый текст (то, что увидит читатель и по чему Telegram считает длину).
+    pub fn visible(&self) -> String {
+        self.spans.iter().map(|s| s.text.as_str()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.iter().all(|s| s.text.trim().is_empty())
+    }
+
+    /// Делит подпись по видимой длине (см. [`cut_point`]), сохраняя оформление.
+    pub fn split(&self, limit: usize) -> (Formatted, Option<Formatted>) {
+        let visible = self.visible();
+        let Some(cut) = cut_point(&visible, limit) else {
+            return (self.clone(), None);
+        };
+        let mut head = Formatted::default();
+        let mut tail = Formatted::default();
+        let mut offset = 0;
+        for span in &self.spans {
+            let (start, end) = (offset, offset + span.text.len());
+            offset = end;
+            if start < cut.head_end {
+                let to = end.min(cut.head_end) - start;
+                head.push(span.text[..to].to_string(), span.style.clone());
+            }
+            if end > cut.tail_start {
+                let from = cut.tail_start.max(start) - start;
+                tail.push(span.text[from..].to_string(), span.style.clone());
+            }
+        }
+        if cut.ellipsis {
+            head.push("…".to_string(), Style::Plain);
+        }
+        let tail = (!tail.is_empty()).then_some(tail);
+        (head, tail)
+    }
+
+    /// Склеивает подписи через разделитель `sep` (обычный текст).
+    pub fn join(parts: Vec<Formatted>, sep: &str) -> Formatted {
+        let mut out = Formatted::default();
+        for (i, part) in parts.into_iter().filter(|p| !p.is_empty()).enumerate() {
+            if i > 0 {
+                out.push(sep.to_string(), Style::Plain);
+            }
+            for span in part.spans {
+                out.push(span.text, span.style);
+            }
+        }
+        out
+    }
+
+    /// Раскладывает две подписи (например, на двух языках) в одну не длиннее
+    /// `limit`: если целиком не помещаются, каждая получает свою долю лимита
+    /// (неиспользованную долю короткой забирает длинная), а остатки обеих идут
+    /// в хвост по порядку.
+    pub fn split_pair(
+        first: &Formatted,
+        second: &Formatted,
+        sep: &str,
+        limit: usize,
+    ) -> (Formatted, Option<Formatted>) {
+        let whole = Formatted::join(vec![first.clone(), second.clone()], sep);
+        if utf16_len(&whole.visible()) <= limit {
+            return (whole, None);
+        }
+        let budget = limit.saturating_sub(utf16_len(sep));
+        let half = budget / 2;
+        let (first_len, second_len) = (utf16_len(&first.visible()), utf16_len(&second.visible()));
+        let first_limit = if first_len <= half {
+            first_len
+        } else if second_len <= half {
+            budget - second_len
+        } else {
+            half
+        };
+        let (first_head, first_tail) = first.split(first_limit);
+        let (second_head, second_tail) = second.split(budget - first_limit);
+        let head = Formatted::join(vec![first_head, second_head], sep);
+        let tail = Formatted::join(first_tail.into_iter().chain(second_tail).collect(), sep);
+        let tail = (!tail.is_empty()).then_some(tail);
+        (head, tail)
+    }
+
+    /// Делит подпись на части не длиннее `limit` видимых символов.
+    pub fn split_all(&self, limit: usize) -> Vec<Formatted> {
+        let mut parts = Vec::new();
+        let mut rest = Some(self.clone());
+        while let Some(current) = rest.take() {
+            let (head, tail) = current.split(limit);
+            if !head.is_empty() {
+                parts.push(head);
+            }
+            rest = tail;
+        }
+        parts
+    }
+
+    /// Выводит подпись в выбранном формате с экранированием.
+    pub fn render(&self, format: CaptionFormat) -> String {
+        let mut out = String::new();
+        for span in &self.spans {
+            match format {
+                CaptionFormat::Plain => out.push_str(&span.text),
+                CaptionFormat::Html => {
+                    let text = escape_html(&span.text);
+                    match &span.style {
+                        Style::Plain => out.push_str(&text),
+                        Style::Bold => out.push_str(&format!("<b>{}</b>", text)),
+                        Style::Italic => out.push_str(&format!("<i>{}</i>", text)),
+                        Style::Link(url) => out.push_str(&format!(
+                            "<a href=\"{}\">{}</a>",
+                            escape_html(url).replace('"', "&quot;"),
+                            text
+                        )),
+                    }
+                }
+                CaptionFormat::MarkdownV2 => {
+                    let text = escape_markdown(&span.text);
+                    match &span.style {
+                        Style::Plain => out.push_str(&text),
+                        Style::Bold => out.push_str(&format!("*{}*", text)),
+                        Style::Italic => out.push_str(&format!("_{}_", text)),
+                        Style::Link(url) => out.push_str(&format!(
+                            "[{}]({})",
+                            text,
+                            url.replace('\\', "\\\\").replace(')', "\\)")
+                        )),
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Разбирает `[текст](url)` в начале `chars`. `Ok(None)` — это не ссылка.
+fn parse_link(chars: &[char]) -> Result<Option<(String, String, usize)>, MarkupError> {
+    let Some(close) = chars.iter().position(|&c| c == ']') else {
+        return Ok(None);
+    };
+    if chars.get(close + 1) != Some(&'(') {
+        return Ok(None);
+    }
+    let text: String = chars[1..close].iter().collect();
+    let rest = &chars[close + 2..];
+    let Some(end) = rest.iter().position(|&c| c == ')') else {
+        return Err(MarkupError::BadLink(text));
+    };
+    let url: String = rest[..end].iter().collect();
+    let url = url.trim().to_string();
+    let valid_scheme = ["https://", "http://", "tg://"]
+        .iter()
+        .any(|s| url.starts_with(s));
+    if text.trim().is_empty() || !valid_scheme || url.chars().any(char::is_whitespace) {
+        return Err(MarkupError::BadLink(url));
+    }
+    Ok(Some((text, url, close + 2 + end + 1)))
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn escape_markdown(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "_*[]()~`>#+-=|{}.!\\".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_html_with_escaping() {
+        let f = Formatted::parse(
+            "**Закат <у> моря**\n\nИстория & *подпись* [сайт](https://example.com/?a=1&b=\"2\")",
+        )
+        .unwrap();
+        assert_eq!(
+            f.render(CaptionFormat::Html),
+            "<b>Закат &lt;у&gt; моря</b>\n\nИстория &amp; <i>подпись</i> <a href=\"https://example.com/?a=1&amp;b=&quot;2&quot;\">сайт</a>"
+        );
+        assert_eq!(f.visible(), "Закат <у> моря\n\nИстория & подпись сайт");
+    }
+
+    #[test]
+    fn renders_markdown_v2_with_escaping() {
+        let f = Formatted::parse("**Пионы!** _акварель_, 30x40 (2024).").unwrap();
+        assert_eq!(
+            f.render(CaptionFormat::MarkdownV2),
+            "*Пионы\\!* _акварель_, 30x40 \\(2024\\)\\."
+        );
+    }
+
+    #[test]
+    fn underscores_inside_words_are_literal() {
+        let f = Formatted::parse("file_name_here").unwrap();
+        assert_eq!(f.render(CaptionFormat::Html), "file_name_here");
+    }
+
+    #[test]
+    fn invalid_markup_falls_back_to_plain() {
+        assert_eq!(
+            Formatted::parse("**не закрыто"),
+            Err(MarkupError::Unclosed("**"))
+        );
+        assert!(matches!(
+            Formatted::parse("[ссылка](javascript:alert(1))"),
+            Err(MarkupError::BadLink(_))
+        ));
+        let (f, err) = Formatted::parse_or_plain("**не закрыто <b>");
+        assert!(err.is_some());
+        assert_eq!(f.render(CaptionFormat::Plain), "не закрыто <b>");
+    }
+
+    #[test]
+    fn split_pair_shares_the_limit() {
+        let ru = Formatted::parse("Первая фраза. Вторая фраза. Третья фраза.").unwrap();
+        let en = Formatted::parse("**Title.** Short.").unwrap();
+        let (head, tail) = Formatted::split_pair(&ru, &en, "\n\n", 40);
+        assert_eq!(
+            head.render(CaptionFormat::Html),
+            "Первая фраза.\n\n<b>Title.</b> Short."
+        );
+        assert_eq!(tail.unwrap().visible(), "Вторая фраза. Третья фраза.");
+        let (head, tail) = Formatted::split_pair(&ru, &en, " / ", 100);
+        assert_eq!(
+            head.visible(),
+            "Первая фраза. Вторая фраза. Третья фраза. / Title. Short."
+        );
+        assert!(tail.is_none());
+    }
+
+    #[test]
+    fn split_keeps_styles_and_measures_visible_text() {
+        let f = Formatted::parse("**Название.** Первая фраза. Вторая фраза.").unwrap();
+        let (head, tail) = f.split(28);
+        assert_eq!(
+            head.render(CaptionFormat::Html),
+            "<b>Название.</b> Первая фраза."
+        );
+        assert_eq!(tail.unwrap().render(CaptionFormat::Html), "Вторая фраза.");
+    }
+}
@@ -0,0 +1,186 @@
+// Параметры генерации (температура, top_p, лимит токенов, детализация
+// изображения) для модели и персоны: `model_params` и `persona_params`.
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::prompts::list_personas;
+
+/// Как называется лимит токенов ответа в теле запроса.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenParam {
+    /// `max_tokens` — классические модели Chat Completions.
+    MaxTokens,
+    /// `max_completion_tokens` — новые модели, отклоняющие `max_tokens`.
+    MaxCompletionTokens,
+}
+
+impl TokenParam {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenParam::MaxTokens => "max_tokens",
+            TokenParam::MaxCompletionTokens => "max_completion_tokens",
+        }
+    }
+}
+
+/// Детализация изображения для Vision (`detail` в `image_url`).
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageDetail {
+    Low,
+    High,
+    Auto,
+}
+
+impl ImageDetail {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ImageDetail::Low => "low",
+            ImageDetail::High => "high",
+            ImageDetail::Auto => "auto",
+        }
+    }
+}
+
+/// Параметры генерации; незаданные поля берутся с уровня ниже
+/// (встроенные значения → модель → персона).
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct GenParams {
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub token_param: Option<TokenParam>,
+    #[serde(default)]
+    pub detail: Option<ImageDetail>,
+}
+
+impl GenParams {
+    /// Встроенные значения: прежние `temperature: 0.9` и `max_tokens: 400`.
+    pub fn defaults() -> Self {
+        Self {
+            temperature: Some(0.9),
+            top_p: None,
+            max_tokens: Some(400),
+            token_param: Some(TokenParam::MaxTokens),
+            detail: None,
+        }
+    }
+
+    /// Накладывает `other` поверх текущих параметров.
+    pub fn merge(self, other: GenParams) -> Self {
+        Self {
+            temperature: other.temperature.or(self.temperature),
+            top_p: other.top_p.or(self.top_p),
+            max_tokens: other.max_tokens.or(self.max_tokens),
+            token_param: other.token_param.or(self.token_param),
+            detail: other.detail.or(self.detail),
+        }
+    }
+
+    /// Проверяет диапазоны значений; `what` — где заданы параметры (для сообщения).
+    fn validate(&self, what: &str) -> Result<()> {
+        if let Some(t) = self.temperature {
+            if !(0.0..=2.0).contains(&t) {
+                bail!("{}: temperature должна быть от 0 до 2, задано {}", what, t);
+            }
+        }
+        if let Some(p) = self.top_p {
+            if !(p > 0.0 && p <= 1.0) {
+                bail!("{}: top_p должен быть в (0, 1], задано {}", what, p);
+            }
+        }
+        if self.max_tokens == Some(0) {
+            bail!("{}: max_tokens должен быть больше нуля", what);
+        }
+        Ok(())
+    }
+}
+
+/// Значение для модели: точное совпадение имени или самый длинный префикс
+/// (`gpt-4o` подходит для `gpt-4o-2024-08-06`).
+pub fn by_model<'a, T>(map: &'a HashMap<String, T>, model: &str) -> Option<&'a T> {
+    map.get(model).or_else(|| {
+        map.iter()
+            .filter(|(name, _)| model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, value)| value)
+    })
+}
+
+/// Параметры модели `model` поверх встроенных значений и общего `vision_detail`.
+pub fn for_model(config: &Config, model: &str) -> GenParams {
+    let base = GenParams {
+        detail: config.vision_detail,
+        ..GenParams::defaults()
+    };
+    let params = by_model(&config.model_params, model)
+        .copied()
+        .unwrap_or_default();
+    base.merge(params)
+}
+
+/// Проверка `model_params` и `persona_params` при старте: диапазоны значений
+/// и то, что персоны существуют в `prompts_dir`.
+pub fn validate(config: &Config) -> Result<()> {
+    for (model, params) in &config.model_params {
+        params.validate(&format!("model_params.{}", model))?;
+    }
+    let personas = list_personas(config);
+    for (persona, params) in &config.persona_params {
+        params.validate(&format!("persona_params.{}", persona))?;
+        if config.prompts_dir.is_some() && !personas.contains(persona) {
+            bail!(
+                "persona_params.{}: такой персоны нет в prompts_dir",
+                persona
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+
+    #[test]
+    fn persona_overrides_model_overrides_defaults() {
+        let config = test_config(serde_json::json!({
+            "vision_detail": "high",
+            "model_params": {
+                "gpt-4o": {"detail": "low", "temperature": 0.7},
+                "o4": {"token_param": "max_completion_tokens", "max_tokens": 2000}
+            }
+        }));
+        let model = for_model(&config, "gpt-4o-2024-08-06");
+        assert_eq!(model.detail, Some(ImageDetail::Low));
+        assert_eq!(model.max_tokens, Some(400));
+        let persona = GenParams {
+            temperature: Some(1.1),
+            ..Default::default()
+        };
+        assert_eq!(model.merge(persona).temperature, Some(1.1));
+        let o4 = for_model(&config, "o4-mini");
+        assert_eq!(o4.token_param, Some(TokenParam::MaxCompletionTokens));
+        assert_eq!(o4.detail, Some(ImageDetail::High));
+        assert_eq!(o4.temperature, Some(0.9));
+    }
+
+    #[test]
+    fn rejects_out_of_range() {
+        let bad = GenParams {
+            top_p: Some(1.5),
+            ..Default::default()
+        };
+        assert!(bad.validate("x").is_err());
+        assert!(GenParams::defaults().validate("x").is_ok());
+    }
+}
@@ -1,10 +1,20 @@
-use anyhow::{anyhow, Context, Result};
+use anyhow::{Context, Result};
 use base64::{engine::general_purpose, Engine as _};
+use futures::future::join_all;
 use image::ImageFormat;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
 use serde_json::json;
 
-use crate::config::Config;
-use crate::logging::{compact, log, Level};
+use crate::ai_http::{build_client, is_content_filter, send_json, GenError, RetryPolicy};
+use crate::config::{Config, VariantsMode};
+use crate::gen_params::{for_model, GenParams, TokenParam};
+use crate::logging::{log, Level};
+use crate::provider::{
+    collect_variants, variant_temperatures, BoxFuture, CaptionProvider, CaptionRequest, Generation,
+    Usage, TEXT_PARAMS,
+};
+use crate::structured;
 
 const DEFAULT_SYSTEM_PROMPT: &str = "
 Когда отвечаешь не переспрашивай что дальше делать, не делай предложений. Ты генерируешь описание для поста в соцсеть.
@@ -17,6 +27,13 @@ const DEFAULT_SYSTEM_PROMPT: &str = "
 Должно получиться четыре абзаца текста
 ";
 
+/// Системный промпт из конфига или встроенный по умолчанию.
+pub fn system_prompt(cfg: &Config) -> String {
+    cfg.openai_system_prompt
+        .clone()
+        .unwrap_or_else(|| DEFAULT_SYSTEM_PROMPT.to_string())
+}
+
 /// Определяет MIME‑тип по сигнатуре изображения.
 /// Функция определяет MIME‑тип изображения по его байтам.
 fn guess_mime(bytes: &[u8]) -> &'static str {
@@ -31,79 +48,418 @@ fn guess_mime(bytes: &[u8]) -> &'static str {
     }
 }
 
-/// Генерирует подпись через OpenAI Vision: отправляем картинку как data URL
-/// и системный промпт под акварельные работы. Результат укорачиваем,
-/// чтобы уложиться в лимит подписи Telegram.
-/// Функция генерирует подпись с помощью OpenAI Vision по данным `stats` и байтам изображения.
-pub async fn generate_caption_openai_vision(bytes: &[u8], cfg: &Config) -> Result<String> {
-    let api_key = cfg
-        .openai_api_key
-        .clone()
-        .context("параметр openai_api_key не задан в конфиге")?;
-    let model = cfg
-        .openai_vision_model
-        .clone()
-        .unwrap_or_else(|| cfg.openai_model.clone());
-    let base = cfg.openai_base.clone();
-    log("openai", "vision", Level::Debug, "Запрос к OpenAI Vision")
-        .data("model", model.clone())
-        .data("base", base.clone())
-        .print();
+/// Запрос без изображения (`openai_use_vision: false`).
+const TEXT_ONLY_REQUEST: &str = "Составь подпись для новой акварельной работы.";
 
-    let system = cfg
-        .openai_system_prompt
-        .clone()
-        .unwrap_or_else(|| DEFAULT_SYSTEM_PROMPT.to_string());
-
-    // Инлайн‑вставка изображения через data URL, чтобы обойтись без внешнего хостинга
-    let mime = guess_mime(bytes);
-    let b64 = general_purpose::STANDARD.encode(bytes);
-    let data_url = format!("data:{};base64,{}", mime, b64);
-
-    // Тело Chat Completions запроса (Vision поддерживается через тип content=image_url)
-    let body = json!({
-        "model": model,
-        "temperature": 0.9,
-        "max_tokens": 400,
-        "messages": [
-            {"role": "system", "content": system},
-            {"role": "user", "content": [
-                {"type": "image_url", "image_url": {"url": data_url}}
-            ]}
-        ]
-    });
-
-    let client = reqwest::Client::new();
-    let resp = client
-        .post(format!("{}/v1/chat/completions", base))
-        .bearer_auth(api_key)
-        .header(reqwest::header::CONTENT_TYPE, "application/json")
-        .json(&body)
-        .send()
-        .await
-        .context("ошибка запроса к OpenAI Vision")?;
-
-    let status = resp.status();
-    let val: serde_json::Value = resp.json().await.context("некорректный JSON от OpenAI")?;
-    if !status.is_success() {
-        log("openai", "vision", Level::Warn, "Ошибка OpenAI Vision")
-            .data("status", status.to_string())
-            .data("body", compact(&val.to_string(), 200))
+/// Какой API OpenAI использовать.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OpenAiApi {
+    /// Chat Completions (`/v1/chat/completions`).
+    #[default]
+    Chat,
+    /// Responses API (`/v1/responses`).
+    Responses,
+}
+
+impl OpenAiApi {
+    fn path(self) -> &'static str {
+        match self {
+            OpenAiApi::Chat => "chat/completions",
+            OpenAiApi::Responses => "responses",
+        }
+    }
+}
+
+/// Как передаётся ключ API.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpenAiAuth {
+    /// `Authorization: Bearer <ключ>`.
+    #[default]
+    #[serde(rename = "bearer")]
+    Bearer,
+    /// Заголовок `api-key: <ключ>` (Azure OpenAI).
+    #[serde(rename = "api-key", alias = "api_key")]
+    ApiKey,
+}
+
+/// Провайдер OpenAI: Chat Completions или Responses API (с Vision или только
+/// текст), в том числе через Azure и совместимые шлюзы.
+pub struct OpenAiProvider {
+    client: reqwest::Client,
+    retry: RetryPolicy,
+    api_key: String,
+    api: OpenAiApi,
+    /// Адрес запроса: `openai_endpoint` или `{openai_base}/v1/...`.
+    url: String,
+    auth: OpenAiAuth,
+    api_version: Option<String>,
+    /// Дополнительные заголовки из `openai_headers`.
+    headers: HeaderMap,
+    model: String,
+    use_vision: bool,
+    variants_mode: VariantsMode,
+    /// Параметры генерации модели (`model_params` поверх встроенных).
+    params: GenParams,
+}
+
+impl OpenAiProvider {
+    pub fn from_config(cfg: &Config) -> Result<Self> {
+        let api_key = cfg
+            .openai_api_key
+            .clone()
+            .context("параметр openai_api_key не задан в конфиге")?;
+        let use_vision = cfg.openai_use_vision.unwrap_or(true);
+        let model = if use_vision {
+            cfg.openai_vision_model
+                .clone()
+                .unwrap_or_else(|| cfg.openai_model.clone())
+        } else {
+            cfg.openai_model.clone()
+        };
+        let mut headers = HeaderMap::new();
+        for (name, value) in &cfg.openai_headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("некорректное имя заголовка openai_headers: {}", name))?;
+            let value = HeaderValue::from_str(value).with_context(|| {
+                format!("некорректное значение заголовка openai_headers.{}", name)
+            })?;
+            headers.insert(name, value);
+        }
+        let url = cfg.openai_endpoint.clone().unwrap_or_else(|| {
+            format!(
+                "{}/v1/{}",
+                cfg.openai_base.trim_end_matches('/'),
+                cfg.openai_api.path()
+            )
+        });
+        Ok(Self {
+            client: build_client(cfg),
+            retry: RetryPolicy::from_config(cfg),
+            api_key,
+            api: cfg.openai_api,
+            url,
+            auth: cfg.openai_auth,
+            api_version: cfg.openai_api_version.clone(),
+            headers,
+            use_vision,
+            variants_mode: cfg.caption_variants_mode,
+            params: for_model(cfg, &model),
+            model,
+        })
+    }
+
+    /// Генерирует `n` вариантов подписи через OpenAI Vision: отправляем картинку
+    /// как data URL и системный промпт под акварельные работы. Chat Completions
+    /// возвращает все варианты одним запросом, Responses API — по запросу на вариант.
+    async fn generate_captions(
+        &self,
+        req: &CaptionRequest<'_>,
+        n: usize,
+    ) -> Result<Vec<Generation>> {
+        log("openai", "vision", Level::Debug, "Запрос к OpenAI Vision")
+            .data("model", self.model.clone())
+            .data("url", self.url.clone())
+            .data("api", format!("{:?}", self.api))
+            .data("vision", self.use_vision.to_string())
             .print();
-        return Err(anyhow!("openai error: {}", val));
-    }
-    // Достаём текст ассистента и ограничиваем ~1000 символов
-    let content = val["choices"][0]["message"]["content"]
-        .as_str()
-        .ok_or_else(|| anyhow!("openai response missing content"))?;
-    let capped = content.chars().take(1000).collect::<String>();
-    log(
-        "openai",
-        "vision",
-        Level::Debug,
-        "Ответ OpenAI Vision обработан",
-    )
-    .data("len", capped.len().to_string())
-    .print();
-    Ok(capped)
+
+        let params = self.params.merge(req.params);
+        // Инлайн‑вставка изображения через data URL, чтобы обойтись без внешнего хостинга
+        let image = self.use_vision.then(|| {
+            let mime = guess_mime(req.image);
+            let b64 = general_purpose::STANDARD.encode(req.image);
+            format!("data:{};base64,{}", mime, b64)
+        });
+        let detail = params.detail.map(|d| d.as_str());
+
+        match self.api {
+            OpenAiApi::Chat => {
+                let user_content = match image {
+                    Some(url) => {
+                        let mut image_url = json!({"url": url});
+                        if let Some(detail) = detail {
+                            image_url["detail"] = json!(detail);
+                        }
+                        json!([{"type": "image_url", "image_url": image_url}])
+                    }
+                    None => json!(TEXT_ONLY_REQUEST),
+                };
+                // Тело Chat Completions запроса (Vision поддерживается через тип content=image_url)
+                let mut body = self.chat_body(req.system_prompt, user_content, params);
+                if req.structured {
+                    body["response_format"] = json!({
+                        "type": "json_schema",
+                        "json_schema": {"name": "caption", "strict": true, "schema": structured::schema()}
+                    });
+                }
+                if n > 1 {
+                    body["n"] = json!(n);
+                }
+                let val = self.post(&body).await?;
+                self.parse_choices(&val)
+            }
+            OpenAiApi::Responses => {
+                let input = match image {
+                    Some(url) => {
+                        let mut part = json!({"type": "input_image", "image_url": url});
+                        if let Some(detail) = detail {
+                            part["detail"] = json!(detail);
+                        }
+                        json!([{"role": "user", "content": [part]}])
+                    }
+                    None => json!(TEXT_ONLY_REQUEST),
+                };
+                let mut body = self.responses_body(req.system_prompt, input, params);
+                if req.structured {
+                    body["text"] = json!({
+                        "format": {
+                            "type": "json_schema",
+                            "name": "caption",
+                            "strict": true,
+                            "schema": structured::schema()
+                        }
+                    });
+                }
+                // В Responses API нет параметра `n`: варианты — отдельными запросами
+                let results = join_all((0..n.max(1)).map(|_| self.send(&body))).await;
+                collect_variants(&self.name(), results)
+            }
+        }
+    }
+
+    /// Тело запроса Chat Completions.
+    fn chat_body(
+        &self,
+        system: &str,
+        user: serde_json::Value,
+        params: GenParams,
+    ) -> serde_json::Value {
+        let mut body = json!({
+            "model": self.model,
+            "messages": [
+                {"role": "system", "content": system},
+                {"role": "user", "content": user}
+            ]
+        });
+        let tokens = params.token_param.unwrap_or(TokenParam::MaxTokens).as_str();
+        apply_params(&mut body, params, tokens);
+        body
+    }
+
+    /// Тело запроса Responses API: системный промпт — в `instructions`.
+    fn responses_body(
+        &self,
+        system: &str,
+        input: serde_json::Value,
+        params: GenParams,
+    ) -> serde_json::Value {
+        let mut body = json!({
+            "model": self.model,
+            "instructions": system,
+            "input": input
+        });
+        apply_params(&mut body, params, "max_output_tokens");
+        body
+    }
+
+    /// Отправляет запрос на `url` с ключом по `openai_auth`, `api-version` и
+    /// заголовками `openai_headers`.
+    async fn post(&self, body: &serde_json::Value) -> Result<serde_json::Value> {
+        let val = send_json("openai", self.retry, || {
+            let req = self
+                .client
+                .post(&self.url)
+                .headers(self.headers.clone())
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .json(body);
+            let req = match self.auth {
+                OpenAiAuth::Bearer => req.bearer_auth(&self.api_key),
+                OpenAiAuth::ApiKey => req.header("api-key", &self.api_key),
+            };
+            match &self.api_version {
+                Some(version) => req.query(&[("api-version", version)]),
+                None => req,
+            }
+        })
+        .await
+        .inspect_err(|err| {
+            log("openai", "vision", Level::Warn, "Ошибка OpenAI Vision")
+                .data("error", err.to_string())
+                .print();
+        })?;
+        Ok(val)
+    }
+
+    /// Тексты ответа (варианты, отсечённые фильтром контента, пропускаются);
+    /// расход токенов запроса приписывается первому.
+    fn parse_choices(&self, val: &serde_json::Value) -> Result<Vec<Generation>> {
+        let choices = val["choices"].as_array().cloned().unwrap_or_default();
+        let mut captions = Vec::new();
+        for choice in &choices {
+            if is_content_filter(choice["finish_reason"].as_str().unwrap_or_default()) {
+                continue;
+            }
+            // Достаём текст ассистента; под лимит Telegram он подгоняется при публикации
+            if let Some(content) = choice["message"]["content"].as_str() {
+                captions.push(Generation::text(content.trim().to_string()));
+            }
+        }
+        if captions.is_empty() {
+            if choices
+                .iter()
+                .any(|c| is_content_filter(c["finish_reason"].as_str().unwrap_or_default()))
+            {
+                return Err(GenError::ContentFilter {
+                    message: "finish_reason=content_filter".to_string(),
+                }
+                .into());
+            }
+            return Err(GenError::BadResponse {
+                status: 200,
+                message: "openai response missing content".to_string(),
+            }
+            .into());
+        }
+        log(
+            "openai",
+            "vision",
+            Level::Debug,
+            "Ответ OpenAI Vision обработан",
+        )
+        .data("variants", captions.len().to_string())
+        .data("len", captions[0].text.len().to_string())
+        .print();
+        captions[0].usage = openai_usage(val, &self.model);
+        Ok(captions)
+    }
+
+    /// Текст ответа Responses API (части `output_text` сообщений ассистента).
+    fn parse_output(&self, val: &serde_json::Value) -> Result<Generation> {
+        if val["incomplete_details"]["reason"].as_str() == Some("content_filter") {
+            return Err(GenError::ContentFilter {
+                message: "incomplete_details.reason=content_filter".to_string(),
+            }
+            .into());
+        }
+        let mut text = String::new();
+        let mut refusal = None;
+        let output = val["output"].as_array().cloned().unwrap_or_default();
+        for item in output.iter().filter(|i| i["type"] == "message") {
+            for part in item["content"].as_array().into_iter().flatten() {
+                match part["type"].as_str() {
+                    Some("output_text") => text.push_str(part["text"].as_str().unwrap_or_default()),
+                    Some("refusal") => refusal = part["refusal"].as_str().map(str::to_string),
+                    _ => {}
+                }
+            }
+        }
+        if text.trim().is_empty() {
+            if let Some(message) = refusal {
+                return Err(GenError::ContentFilter { message }.into());
+            }
+            return Err(GenError::BadResponse {
+                status: 200,
+                message: "openai response missing output_text".to_string(),
+            }
+            .into());
+        }
+        Ok(Generation {
+            text: text.trim().to_string(),
+            usage: responses_usage(val, &self.model),
+        })
+    }
+
+    /// Один запрос — один ответ (первый вариант для Chat Completions).
+    async fn send(&self, body: &serde_json::Value) -> Result<Generation> {
+        let val = self.post(body).await?;
+        match self.api {
+            OpenAiApi::Chat => Ok(self.parse_choices(&val)?.swap_remove(0)),
+            OpenAiApi::Responses => self.parse_output(&val),
+        }
+    }
+
+    /// Текстовый запрос без изображения.
+    async fn complete_text(&self, system: &str, user: &str) -> Result<Generation> {
+        let params = self.params.merge(TEXT_PARAMS);
+        let body = match self.api {
+            OpenAiApi::Chat => self.chat_body(system, json!(user), params),
+            OpenAiApi::Responses => self.responses_body(system, json!(user), params),
+        };
+        self.send(&body).await
+    }
+
+    async fn generate_caption(&self, req: &CaptionRequest<'_>) -> Result<Generation> {
+        let mut captions = self.generate_captions(req, 1).await?;
+        Ok(captions.swap_remove(0))
+    }
+}
+
+/// Температура, top_p и лимит токенов в теле запроса; `tokens` — имя поля
+/// лимита (по `token_param` модели или `max_output_tokens` для Responses API).
+fn apply_params(body: &mut serde_json::Value, params: GenParams, tokens: &str) {
+    if let Some(t) = params.temperature {
+        body["temperature"] = json!(t);
+    }
+    if let Some(p) = params.top_p {
+        body["top_p"] = json!(p);
+    }
+    if let Some(n) = params.max_tokens {
+        body[tokens] = json!(n);
+    }
+}
+
+/// Блок `usage` ответа OpenAI.
+fn openai_usage(val: &serde_json::Value, model: &str) -> Option<Usage> {
+    let usage = val.get("usage")?;
+    Some(Usage {
+        model: val["model"].as_str().unwrap_or(model).to_string(),
+        prompt_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0),
+        completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+    })
+}
+
+/// Блок `usage` ответа Responses API.
+fn responses_usage(val: &serde_json::Value, model: &str) -> Option<Usage> {
+    let usage = val.get("usage")?;
+    Some(Usage {
+        model: val["model"].as_str().unwrap_or(model).to_string(),
+        prompt_tokens: usage["input_tokens"].as_u64().unwrap_or(0),
+        completion_tokens: usage["output_tokens"].as_u64().unwrap_or(0),
+    })
+}
+
+impl CaptionProvider for OpenAiProvider {
+    fn name(&self) -> String {
+        format!("openai:{}", self.model)
+    }
+
+    fn generate<'a>(&'a self, req: &'a CaptionRequest<'a>) -> BoxFuture<'a, Result<Generation>> {
+        Box::pin(self.generate_caption(req))
+    }
+
+    fn complete<'a>(&'a self, system: &'a str, user: &'a str) -> BoxFuture<'a, Result<Generation>> {
+        Box::pin(self.complete_text(system, user))
+    }
+
+    /// С `caption_variants_mode: "n"` все варианты приходят одним запросом.
+    fn generate_variants<'a>(
+        &'a self,
+        req: &'a CaptionRequest<'a>,
+        n: usize,
+    ) -> BoxFuture<'a, Result<Vec<Generation>>> {
+        Box::pin(async move {
+            match self.variants_mode {
+                VariantsMode::N => self.generate_captions(req, n).await,
+                VariantsMode::Parallel => {
+                    let requests: Vec<CaptionRequest<'a>> = variant_temperatures(n)
+                        .into_iter()
+                        .map(|t| req.with_temperature(t))
+                        .collect();
+                    let results = join_all(requests.iter().map(|r| self.generate_caption(r))).await;
+                    collect_variants(&self.name(), results)
+                }
+            }
+        })
+    }
 }
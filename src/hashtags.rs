@@ -0,0 +1,228 @@
+// Хэштеги к подписи: теги, предложенные моделью, из сопроводительного файла и
+// палитры сводятся к словарю `hashtags_file`, чтобы в сотнях постов они были
+// единообразными.
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::logging::{log, Level};
+
+/// Хэштег: `#`, затем буквы, цифры и `_`, хотя бы одна буква (`#1` и `##` — не теги).
+static HASHTAG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^#[\p{L}\p{N}_]*\p{L}[\p{L}\p{N}_]*$").unwrap());
+
+/// Хэштег внутри строки текста: `#` в начале слова, затем тег.
+static INLINE_HASHTAG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(^|\s)#([\p{L}\p{N}_]*\p{L}[\p{L}\p{N}_]*)\b").unwrap());
+
+/// Файл словаря хэштегов: разрешённые теги и замены для синонимов.
+#[derive(Debug, Deserialize)]
+struct DictionaryFile {
+    /// Разрешённые теги (без `#`).
+    #[serde(default)]
+    tags: Vec<String>,
+    /// Синоним → тег из `tags`, например `"watercolor": "акварель"`.
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+}
+
+/// Словарь хэштегов, приведённый к виду для поиска при загрузке.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(from = "DictionaryFile")]
+pub struct Dictionary {
+    /// Разрешённые теги, как они записаны в файле (для подсказки модели).
+    pub tags: Vec<String>,
+    /// Нормализованные разрешённые теги.
+    allowed: HashSet<String>,
+    /// Нормализованный синоним → нормализованный тег.
+    aliases: HashMap<String, String>,
+}
+
+impl From<DictionaryFile> for Dictionary {
+    fn from(file: DictionaryFile) -> Self {
+        Self {
+            allowed: file.tags.iter().filter_map(|t| normalize(t)).collect(),
+            aliases: file
+                .aliases
+                .iter()
+                .filter_map(|(k, v)| Some((normalize(k)?, normalize(v)?)))
+                .collect(),
+            tags: file.tags,
+        }
+    }
+}
+
+impl Dictionary {
+    /// Тег из словаря для кандидата: сам тег, его замена или `None`.
+    fn resolve(&self, candidate: &str) -> Option<String> {
+        let key = normalize(candidate)?;
+        let tag = self.aliases.get(&key).cloned().unwrap_or(key);
+        self.allowed.contains(&tag).then_some(tag)
+    }
+}
+
+/// Слово — хэштег (а не разметка или номер вроде `##` и `#1`).
+pub fn is_hashtag(word: &str) -> bool {
+    HASHTAG.is_match(word)
+}
+
+/// Читает словарь из `hashtags_file` (один раз, при загрузке конфига); без
+/// настройки хэштеги не добавляются.
+pub fn load_dictionary(config: &Config) -> Result<Option<Dictionary>> {
+    let Some(path) = config.hashtags_file.as_deref() else {
+        return Ok(None);
+    };
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("не удалось прочитать словарь хэштегов {}", path))?;
+    let dict = serde_json::from_str(&raw)
+        .with_context(|| format!("некорректный словарь хэштегов {}", path))?;
+    Ok(Some(dict))
+}
+
+/// Подсказка модели: предложить теги из словаря — последней строкой подписи
+/// или, для структурированного ответа, в поле `hashtags`.
+pub fn prompt_hint(config: &Config, dict: &Dictionary, structured: bool) -> String {
+    let place = if structured {
+        "В hashtags укажи"
+    } else {
+        "Последней строкой предложи"
+    };
+    format!(
+        "\n{} до {} хэштегов через пробел, только из списка: {}.",
+        place,
+        config.hashtags_max,
+        dict.tags
+            .iter()
+            .map(|t| format!("#{}", t))
+            .collect::<Vec<_>>()
+            .join(" ")
+    )
+}
+
+/// Приводит тег к виду словаря: без `#`, строчными, пробелы и дефисы — `_`.
+fn normalize(tag: &str) -> Option<String> {
+    let tag: String = tag
+        .trim()
+        .trim_start_matches('#')
+        .to_lowercase()
+        .chars()
+        .map(|c| if c == ' ' || c == '-' { '_' } else { c })
+        .filter(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    let tag = tag.trim_matches('_').to_string();
+    (!tag.is_empty()).then_some(tag)
+}
+
+/// Отделяет от подписи хэштеги, написанные моделью: текст и сами теги.
+/// Строки из одних хэштегов убираются целиком, а тег внутри предложения
+/// остаётся в тексте словом без `#`. Остальные слова с `#` (`##`, `#1`) не
+/// трогаются.
+fn split_hashtags(caption: &str) -> (String, Vec<String>) {
+    let mut tags = Vec::new();
+    let mut lines = Vec::new();
+    for line in caption.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if !words.is_empty() && words.iter().all(|w| is_hashtag(w)) {
+            tags.extend(words.iter().map(|w| w.to_string()));
+            continue;
+        }
+        tags.extend(
+            INLINE_HASHTAG
+                .captures_iter(line)
+                .map(|c| format!("#{}", &c[2])),
+        );
+        lines.push(INLINE_HASHTAG.replace_all(line, "$1$2").into_owned());
+    }
+    (lines.join("\n").trim_end().to_string(), tags)
+}
+
+/// Сводит кандидатов к словарю: сначала теги из сопроводительного файла,
+/// затем предложенные моделью, затем по палитре; без повторов, не больше `max`.
+fn pick(dict: &Dictionary, sources: &[&[String]], max: usize) -> Vec<String> {
+    let mut picked: Vec<String> = Vec::new();
+    for candidate in sources.iter().flat_map(|s| s.iter()) {
+        match dict.resolve(candidate) {
+            Some(tag) if !picked.contains(&tag) => picked.push(tag),
+            Some(_) => {}
+            None => {
+                log(
+                    "ai",
+                    "hashtags",
+                    Level::Debug,
+                    "Тег не из словаря, пропущен",
+                )
+                .data("tag", candidate.clone())
+                .print();
+            }
+        }
+    }
+    picked.truncate(max);
+    picked
+}
+
+/// Заменяет хэштеги модели в подписи итоговой строкой тегов из словаря.
+pub fn apply(
+    config: &Config,
+    dict: &Dictionary,
+    caption: &str,
+    sidecar_tags: &[String],
+    palette: &[String],
+) -> String {
+    let (text, suggested) = split_hashtags(caption);
+    let tags = pick(
+        dict,
+        &[sidecar_tags, &suggested, palette],
+        config.hashtags_max,
+    );
+    if tags.is_empty() {
+        return text;
+    }
+    let line = tags
+        .iter()
+        .map(|t| format!("#{}", t))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("{}\n\n{}", text, line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dict() -> Dictionary {
+        serde_json::from_value(serde_json::json!({
+            "tags": ["акварель", "цветы", "синий", "сухая_кисть"],
+            "aliases": {"watercolor": "акварель", "Сухая кисть": "сухая_кисть"}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn splits_model_hashtags() {
+        let (text, tags) = split_hashtags("Пионы в саду.\n\n#акварель #цветы\nи #синий вечер");
+        assert_eq!(text, "Пионы в саду.\n\nи синий вечер");
+        assert_eq!(tags, vec!["#акварель", "#цветы", "#синий"]);
+        let (text, tags) = split_hashtags("## Пионы\nРабота #1 из серии, #закат.\n\n#цветы");
+        assert_eq!(text, "## Пионы\nРабота #1 из серии, закат.");
+        assert_eq!(tags, vec!["#закат", "#цветы"]);
+    }
+
+    #[test]
+    fn maps_dedupes_and_caps() {
+        let sidecar = vec!["Watercolor".to_string(), "сухая-кисть".to_string()];
+        let model = vec![
+            "#акварель".to_string(),
+            "#закат".to_string(),
+            "#цветы".to_string(),
+        ];
+        let palette = vec!["синий".to_string()];
+        assert_eq!(
+            pick(&dict(), &[&sidecar, &model, &palette], 3),
+            vec!["акварель", "сухая_кисть", "цветы"]
+        );
+    }
+}
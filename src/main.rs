@@ -1,9 +1,35 @@
 // Основной исполняемый модуль: запускает бота, настраивает логирование,
 // подключает SQLite, поднимает обработчики и фоновые задачи (интервал/крон).
+mod ai_http;
+mod caption;
+mod captioning;
 mod db;
+mod drafts;
+mod examples;
+mod fallback;
+mod footer;
+mod gen_params;
 mod generator;
+mod hashtags;
 mod config;
+mod crop;
+mod critique;
+mod format;
 mod logging;
+mod palette;
+mod policy;
+mod pool;
+mod prompts;
+mod provider;
+mod publish;
+mod repetition;
+mod sidecar;
+mod structured;
+mod translate;
+mod usage;
+mod vision;
+#[cfg(test)]
+mod stub_server;
 
 use anyhow::{Context, Result};
 use teloxide::dispatching::UpdateFilterExt;
@@ -13,10 +39,17 @@ use teloxide::requests::Requester;
 use teloxide::types::PhotoSize;
 use teloxide::utils::command::BotCommands as _; // bring trait into scope for descriptions()
 
-use crate::config::{load_config, Config};
-use crate::db::Db;
-use crate::generator::generate_caption_openai_vision;
-use crate::logging::{compact, init_logging, log, Level};
+use crate::captioning::{
+    check_policy, fallback_caption, generate_variants, CaptionJob, UploaderNote,
+};
+use crate::config::{load_config, Config, FailurePolicy};
+use crate::db::{Db, DraftStatus, NewDraft};
+use crate::logging::{init_logging, log, Level};
+use crate::prompts::list_personas;
+use crate::provider::{build_provider, CaptionProvider};
+use crate::translate::translate_for;
+use crate::publish::{download_photo, notify_published, publish_photo, PhotoSource, Post};
+use crate::sidecar::{load_sidecar, Sidecar};
 use sha2::{Digest, Sha256};
 use time::OffsetDateTime;
 use tokio::time::{interval, Duration};
@@ -51,8 +84,14 @@ async fn main() -> Result<()> {
     log("config", "load", Level::Info, "Загружен JSON-конфиг")
         .data("path", config_path)
         .print();
-    // 3) Инициализировать Telegram‑бота
+    // 3) Инициализировать Telegram‑бота и генератор подписей
     let bot = Bot::new(config.teloxide_token.clone());
+    let provider: std::sync::Arc<dyn CaptionProvider> = std::sync::Arc::from(
+        build_provider(&config).context("не удалось настроить генератор подписей")?,
+    );
+    log("caption", "provider", Level::Info, "Генератор подписей настроен")
+        .data("provider", provider.name())
+        .print();
 
     // 4) Подключить SQLite: открыть/создать базу и применить схему
     let db_path = config.db_path.clone();
@@ -76,8 +115,9 @@ async fn main() -> Result<()> {
         let bot_bg = bot.clone();
         let db_bg = db.clone();
         let config_bg = config.clone();
+        let provider_bg = provider.clone();
         tokio::spawn(async move {
-            run_periodic_poster(bot_bg, db_bg, config_bg, post_interval_secs).await;
+            run_periodic_poster(bot_bg, db_bg, config_bg, provider_bg, post_interval_secs).await;
         });
     } else {
         // Use cron from config if provided
@@ -86,12 +126,24 @@ async fn main() -> Result<()> {
             let bot_bg = bot.clone();
             let db_bg = db.clone();
             let config_bg = config.clone();
+            let provider_bg = provider.clone();
             tokio::spawn(async move {
-                run_cron_poster(bot_bg, db_bg, config_bg, expr).await;
+                run_cron_poster(bot_bg, db_bg, config_bg, provider_bg, expr).await;
             });
         }
     }
 
+    // Фоновые повторы генерации для отложенных черновиков
+    {
+        let bot_bg = bot.clone();
+        let db_bg = db.clone();
+        let config_bg = config.clone();
+        let provider_bg = provider.clone();
+        tokio::spawn(async move {
+            drafts::run_deferred_retry(bot_bg, db_bg, config_bg, provider_bg).await;
+        });
+    }
+
     // 7) Для наглядности — вывести информацию о боте
     match bot.get_me().await {
         Ok(me) => {
@@ -112,18 +164,25 @@ async fn main() -> Result<()> {
         }
     }
 
-    // 8) Сконструировать дерево обработчиков: команды и фото
-    let handler = Update::filter_message()
+    // 8) Сконструировать дерево обработчиков: команды, фото и кнопки черновиков
+    let messages = Update::filter_message()
         .branch(
             dptree::entry()
                 .filter_command::<BotCommand>()
                 .endpoint(handle_commands),
         )
-        .branch(dptree::filter(|msg: Message| msg.photo().is_some()).endpoint(handle_photo));
+        .branch(dptree::filter(|msg: Message| msg.photo().is_some()).endpoint(handle_photo))
+        .branch(
+            dptree::filter(|msg: Message| msg.text().is_some() && msg.reply_to_message().is_some())
+                .endpoint(drafts::handle_draft_reply),
+        );
+    let handler = dptree::entry()
+        .branch(messages)
+        .branch(Update::filter_callback_query().endpoint(drafts::handle_draft_callback));
 
     // 9) Запустить диспетчер: передаём зависимостью `db`
     Dispatcher::builder(bot, handler)
-        .dependencies(dptree::deps![db, config])
+        .dependencies(dptree::deps![db, config, provider])
         .enable_ctrlc_handler()
         .build()
         .dispatch()
@@ -137,13 +196,14 @@ async fn run_periodic_poster(
     bot: Bot,
     db: std::sync::Arc<Db>,
     config: std::sync::Arc<Config>,
+    provider: std::sync::Arc<dyn CaptionProvider>,
     every_secs: u64,
 ) {
     // Простой таймер, который раз в N секунд пытается опубликовать один новый файл
     let mut ticker = interval(Duration::from_secs(every_secs));
     loop {
         ticker.tick().await;
-        if let Err(err) = try_post_from_folder(&bot, &db, &config, &config.files_dir).await {
+        if let Err(err) = try_post_from_folder(&bot, &db, &config, provider.as_ref(), &config.files_dir).await {
             log(
                 "poster",
                 "interval",
@@ -161,6 +221,7 @@ async fn run_cron_poster(
     bot: Bot,
     db: std::sync::Arc<Db>,
     config: std::sync::Arc<Config>,
+    provider: std::sync::Arc<dyn CaptionProvider>,
     cron: String,
 ) {
     // Поддерживаемый формат: "M H * * *", где M и H — число или '*'
@@ -194,7 +255,7 @@ async fn run_cron_poster(
         }
         if cron_match_min_hour(&spec, m as u8, h as u8) {
             last_minute = Some(m);
-            if let Err(err) = try_post_from_folder(&bot, &db, &config, &config.files_dir).await {
+            if let Err(err) = try_post_from_folder(&bot, &db, &config, provider.as_ref(), &config.files_dir).await {
                 log("poster", "cron", Level::Warn, "Ошибка публикации по cron")
                     .data("error", err.to_string())
                     .print();
@@ -237,7 +298,7 @@ fn parse_field_minute_hour(v: &str) -> Result<Option<u8>> {
 /// Проверяет совпадение текущих минут/часов с ограничениями CronMinHour.
 fn cron_match_min_hour(spec: &CronMinHour, minute: u8, hour: u8) -> bool {
     // Совпадение соблюдается, если каждое из ограничений либо пустое, либо равно текущему значению
-    (spec.minute.map_or(true, |m| m == minute)) && (spec.hour.map_or(true, |h| h == hour))
+    (spec.minute.is_none_or(|m| m == minute)) && (spec.hour.is_none_or(|h| h == hour))
 }
 
 /// Пытается найти и опубликовать один новый файл из папки `files_dir`.
@@ -246,6 +307,7 @@ async fn try_post_from_folder(
     bot: &Bot,
     db: &std::sync::Arc<Db>,
     config: &Config,
+    provider: &dyn CaptionProvider,
     files_dir: &str,
 ) -> Result<()> {
     // 1) Убедиться, что задан канал для публикации
@@ -291,21 +353,15 @@ async fn try_post_from_folder(
 
     // 4) Фильтровать по поддерживаемым расширениям
     fn is_image(p: &std::path::Path) -> bool {
-        match p
-            .extension()
+        p.extension()
             .and_then(|s| s.to_str())
             .map(|s| s.to_lowercase())
-        {
-            Some(ext)
-                if matches!(
+            .is_some_and(|ext| {
+                matches!(
                     ext.as_str(),
                     "jpg" | "jpeg" | "png" | "webp" | "gif" | "bmp" | "tiff"
-                ) =>
-            {
-                true
-            }
-            _ => false,
-        }
+                )
+            })
     }
 
     for e in entries {
@@ -340,41 +396,130 @@ async fn try_post_from_folder(
             .print();
             continue;
         }
+        if db.has_open_draft_for_hash(&hash).await? {
+            log(
+                "poster",
+                "files",
+                Level::Debug,
+                "Файл ждёт подписи от администратора, пропускаем",
+            )
+            .data("file", path.display().to_string())
+            .print();
+            continue;
+        }
 
-        // 6) Подготовить подпись: анализ изображения + вызов Vision
-        let caption = match generate_caption_openai_vision(&bytes, config).await {
+        // 6) Подготовить подпись выбранным провайдером; без подписи не публикуем
+        let job = CaptionJob {
+            image: &bytes,
+            image_hash: Some(hash.clone()),
+            channel_id,
+            scheduled: true,
+            sidecar: load_sidecar(&path).await,
+            persona: None,
+            fresh: false,
+            note: None,
+        };
+        let variants = if config.approval_required(true) && config.admin_chat_id.is_some() {
+            config.caption_variants
+        } else {
+            1
+        };
+        let mut captions = match generate_variants(config, db, provider, &job, variants).await {
             Ok(c) => c,
             Err(err) => {
                 log(
                     "poster",
                     "caption",
                     Level::Warn,
-                    "Не удалось сгенерировать подпись, используем пустую",
+                    "Не удалось сгенерировать подпись",
                 )
+                .data("file", path.display().to_string())
+                .data("policy", format!("{:?}", config.caption_failure_scheduled))
                 .data("error", err.to_string())
                 .print();
-                String::new()
+                match (config.caption_failure_scheduled, config.admin_chat_id) {
+                    (FailurePolicy::Template, _) => vec![fallback_caption(config, db, &job).await?],
+                    (FailurePolicy::Ask, Some(admin)) => {
+                        let draft = NewDraft {
+                            chat_id: admin,
+                            channel_id,
+                            file_id: None,
+                            path: Some(path.to_string_lossy().into_owned()),
+                            hash: Some(hash.clone()),
+                            status: DraftStatus::AwaitingText,
+                            scheduled: true,
+                            uploader_note: None,
+                        };
+                        drafts::ask_for_caption(bot, db, draft, None).await?;
+                        return Ok(());
+                    }
+                    // Отложить: файл остаётся в очереди до следующего срабатывания
+                    _ => return Ok(()),
+                }
             }
         };
 
-        // 7) Отправить фото в канал (с диска)
-        let sent = bot
-            .send_photo(
-                teloxide::types::ChatId(channel_id),
-                teloxide::types::InputFile::file(path.clone()),
-            )
-            .caption(caption.clone())
-            .await?;
+        // 7) Если публикации из папки нужно утверждать или подпись не прошла
+        // проверку — отправить подпись администратору
+        let violations = check_policy(config, &captions[0], job.note.as_ref())?;
+        if config.approval_required(true) || !violations.is_empty() {
+            match config.admin_chat_id {
+                Some(admin) => {
+                    let draft = NewDraft {
+                        chat_id: admin,
+                        channel_id,
+                        file_id: None,
+                        path: Some(path.to_string_lossy().into_owned()),
+                        hash: Some(hash.clone()),
+                        status: DraftStatus::Pending,
+                        scheduled: true,
+                        uploader_note: None,
+                    };
+                    drafts::propose(bot, db, config, draft, &captions, None).await?;
+                    return Ok(());
+                }
+                None if !violations.is_empty() => {
+                    // Без администратора подпись с нарушениями не публикуем, а
+                    // заменяем запасной: иначе файл генерировался бы заново на
+                    // каждом срабатывании и не пускал бы остальные
+                    log(
+                        "poster",
+                        "policy",
+                        Level::Warn,
+                        "Подпись не прошла проверку, а admin_chat_id не задан — публикуем запасную подпись",
+                    )
+                    .data("file", path.display().to_string())
+                    .data("violations", policy::describe(&violations))
+                    .print();
+                    captions = vec![fallback_caption(config, db, &job).await?];
+                }
+                None => {
+                    log(
+                        "poster",
+                        "files",
+                        Level::Warn,
+                        "post_require_approval без admin_chat_id, публикуем без утверждения",
+                    )
+                    .print();
+                }
+            }
+        }
 
-        // 8) Извлечь Telegram file_id итогового фото (если есть)
-        let file_id = sent
-            .photo()
-            .and_then(|v| v.last())
-            .map(|p| p.file.id.to_string());
+        // 8) Отправить фото в канал (с диска или кадрированный вариант) и записать лог
+        let source = PhotoSource::Path(path.clone());
+        let translation = translate_for(config, db, provider, channel_id, &captions[0]).await?;
+        let post = Post {
+            channel_id,
+            source: &source,
+            bytes: &bytes,
+            caption: &captions[0],
+            scheduled: true,
+            translation: translation.as_ref(),
+            sidecar: &job.sidecar,
+        };
+        publish_photo(bot, db, config, post).await?;
 
-        // 9) Записать лог публикации и сохранить хэш файла
-        db.log_post(channel_id, Some(sent.id.0 as i64), file_id, Some(caption))
-            .await?;
+        // 9) Сохранить хэш файла
         db.insert_file_hash(&hash, path.to_string_lossy().as_ref())
             .await?;
 
@@ -389,7 +534,7 @@ async fn try_post_from_folder(
 }
 
 #[derive(Debug, teloxide::macros::BotCommands, Clone)]
-#[command(description = "Доступные команды:")]
+#[command(rename_rule = "snake_case", description = "Доступные команды:")]
 enum BotCommand {
     #[command(description = "Показать помощь")]
     Help,
@@ -399,14 +544,31 @@ enum BotCommand {
     SetChannel(String),
     #[command(description = "Показать текущие настройки")]
     Settings,
+    #[command(description = "Отменить черновики, ожидающие подписи или утверждения")]
+    Cancel,
+    #[command(description = "Персона подписей: /persona, /persona <имя>, /persona reset")]
+    Persona(String),
+    #[command(description = "Расход токенов и стоимость генераций")]
+    Usage,
+    #[command(description = "Примеры стиля, которые сейчас попадают в промпт")]
+    Examples,
+    #[command(description = "Закрепить пост в примерах стиля: /pin_example <id> [off]")]
+    PinExample(String),
+    #[command(description = "Исключить пост из примеров стиля: /exclude_example <id> [off]")]
+    ExcludeExample(String),
+    #[command(description = "Оценить пост: /rate <id> <1-5>")]
+    Rate(String),
 }
 
-/// Обработчик команд: /help, /start, /set_channel, /settings.
+/// Обработчик команд: /help, /start, /set_channel, /settings, /cancel, /persona, /usage,
+/// а также /examples, /pin_example, /exclude_example и /rate для примеров стиля.
 async fn handle_commands(
     bot: Bot,
     msg: Message,
     cmd: BotCommand,
     db: std::sync::Arc<Db>,
+    config: std::sync::Arc<Config>,
+    provider: std::sync::Arc<dyn CaptionProvider>,
 ) -> Result<()> {
     // Диспетчер команд: логируем и обрабатываем согласно enum BotCommand
     log("tg", "commands", Level::Info, "Получена команда")
@@ -469,28 +631,100 @@ async fn handle_commands(
             }
         }
         BotCommand::Settings => {
-            // Показываем текущий канал из БД
+            // Показываем текущий канал из БД и выбранную персону
             let from_db = db.get_channel_id().await?;
-            let text = match from_db {
+            let mut text = match from_db {
                 Some(id) => format!("Канал: {}", id),
                 None => "Канал не настроен. Используйте /set_channel <id>".to_string(),
             };
+            let persona = db.get_persona().await?.or_else(|| config.persona.clone());
+            text.push_str(&format!(
+                "\nПерсона: {}",
+                persona.as_deref().unwrap_or("по умолчанию")
+            ));
             log("tg", "commands", Level::Debug, "Отправка настроек")
                 .data("chat_id", msg.chat.id.to_string())
                 .print();
             bot.send_message(msg.chat.id, text).await?;
         }
+        BotCommand::Cancel => {
+            // Отменяем незавершённые черновики этого чата
+            let n = db.cancel_open_drafts(msg.chat.id.0).await?;
+            log("tg", "commands", Level::Info, "Черновики отменены")
+                .data("chat_id", msg.chat.id.to_string())
+                .data("count", n.to_string())
+                .print();
+            bot.send_message(msg.chat.id, format!("Отменено черновиков: {}", n))
+                .await?;
+        }
+        BotCommand::Persona(raw) => {
+            // Без аргумента — список персон, иначе выбор или сброс
+            let name = raw.trim();
+            let personas = list_personas(&config);
+            let text = match name {
+                "" => {
+                    let current = db.get_persona().await?.or_else(|| config.persona.clone());
+                    if personas.is_empty() {
+                        "Персоны не найдены: задайте prompts_dir и положите туда <имя>.txt"
+                            .to_string()
+                    } else {
+                        format!(
+                            "Персоны: {}\nТекущая: {}",
+                            personas.join(", "),
+                            current.as_deref().unwrap_or("по умолчанию")
+                        )
+                    }
+                }
+                "reset" => {
+                    db.set_persona(None).await?;
+                    "Персона сброшена.".to_string()
+                }
+                name if personas.iter().any(|p| p == name) => {
+                    db.set_persona(Some(name.to_string())).await?;
+                    format!("Персона выбрана: {}", name)
+                }
+                name => format!("Персона «{}» не найдена. Список: /persona", name),
+            };
+            log("tg", "commands", Level::Info, "Команда /persona")
+                .data("chat_id", msg.chat.id.to_string())
+                .data("value", name)
+                .print();
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        BotCommand::Usage => {
+            // Итоги расхода за день и месяц из таблицы usage и состояние провайдеров
+            let text = usage::report(&config, &db, provider.as_ref()).await?;
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        BotCommand::Examples => {
+            let text = examples::list(&config, &db).await?;
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        BotCommand::PinExample(raw) => {
+            let text = examples::pin(&db, &raw).await?;
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        BotCommand::ExcludeExample(raw) => {
+            let text = examples::exclude(&db, &raw).await?;
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        BotCommand::Rate(raw) => {
+            let text = examples::rate(&db, &raw).await?;
+            bot.send_message(msg.chat.id, text).await?;
+        }
     }
     Ok(())
 }
 
-/// Обработчик входящего фото: скачивает байты, анализирует и генерирует подпись через Vision,
-/// публикует в канал, пишет лог публикации и отправляет подтверждение пользователю.
+/// Обработчик входящего фото: скачивает байты, генерирует подпись выбранным провайдером,
+/// присылает её на утверждение (или сразу публикует в канал, если `require_approval`
+/// выключен) и отправляет подтверждение пользователю.
 async fn handle_photo(
     bot: Bot,
     msg: Message,
     db: std::sync::Arc<Db>,
     config: std::sync::Arc<Config>,
+    provider: std::sync::Arc<dyn CaptionProvider>,
 ) -> Result<()> {
     // Обрабатываем только сообщения с фото
     let Some(photos) = msg.photo() else {
@@ -534,81 +768,103 @@ async fn handle_photo(
     let channel_id = channel_id.unwrap();
 
     // Скачиваем оригинальные байты фото для анализа (Vision получает data URL)
-    let file = bot.get_file(best.file.id.clone()).await?;
-    let file_url = format!(
-        "https://api.telegram.org/file/bot{}/{}",
-        config.teloxide_token,
-        file.path
-    );
-    log("tg", "photo", Level::Debug, "Скачивание изображения")
-        .data("file_url", file_url.clone())
-        .print();
-    let bytes = reqwest::Client::new()
-        .get(file_url)
-        .send()
-        .await
-        .context("не удалось скачать изображение")?
-        .bytes()
-        .await
-        .context("не удалось прочитать байты изображения")?;
-    log("tg", "photo", Level::Debug, "Изображение скачано")
-        .data("size", bytes.len().to_string())
-        .print();
-
-    // Анализ изображения и генерация подписи через OpenAI Vision
-    let caption = match generate_caption_openai_vision(&bytes, &config).await {
-        Ok(c) => {
-            log("ai", "vision", Level::Info, "Подпись сгенерирована")
-                .data("len", c.len().to_string())
-                .data("result", compact(&c, 160))
-                .print();
-            c
-        }
+    let bytes = download_photo(&bot, &config, &best.file.id.to_string()).await?;
+    let source = PhotoSource::FileId(best.file.id.to_string());
+
+    // Подпись к фото: подсказка модели, `!текст` — как есть, `+текст` — дописать
+    let note = msg.caption().map(str::trim).filter(|c| !c.is_empty());
+
+    // Генерация подписи выбранным провайдером; без подписи не публикуем
+    let job = CaptionJob {
+        image: &bytes,
+        image_hash: None,
+        channel_id,
+        scheduled: false,
+        sidecar: Sidecar::default(),
+        persona: None,
+        fresh: false,
+        note: note.and_then(UploaderNote::parse),
+    };
+    let variants = if config.approval_required(false) {
+        config.caption_variants
+    } else {
+        1
+    };
+    let captions = match generate_variants(&config, &db, provider.as_ref(), &job, variants).await {
+        Ok(c) => c,
         Err(err) => {
             log(
                 "ai",
-                "vision",
+                "caption",
                 Level::Error,
-                "Не удалось сгенерировать подпись, используем пустую",
+                "Не удалось сгенерировать подпись",
             )
+            .data("policy", format!("{:?}", config.caption_failure_manual))
             .data("error", err.to_string())
             .print();
-            String::new()
+            let draft = NewDraft {
+                chat_id: msg.chat.id.0,
+                channel_id,
+                file_id: Some(best.file.id.to_string()),
+                path: None,
+                hash: None,
+                status: DraftStatus::AwaitingText,
+                scheduled: false,
+                uploader_note: note.map(str::to_string),
+            };
+            match config.caption_failure_manual {
+                FailurePolicy::Template => vec![fallback_caption(&config, &db, &job).await?],
+                FailurePolicy::Ask => {
+                    drafts::ask_for_caption(&bot, &db, draft, Some(msg.id)).await?;
+                    return Ok(());
+                }
+                FailurePolicy::Defer => {
+                    drafts::defer(&db, draft).await?;
+                    bot.send_message(
+                        msg.chat.id,
+                        "Не удалось сгенерировать подпись. Повторю попытку позже и опубликую пост автоматически.",
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            }
         }
     };
 
-    // Публикуем в канал: переиспользуем file_id исходного фото, чтобы не перезагружать файл
-    log("tg", "photo", Level::Info, "Публикация в канал")
-        .data("channel_id", channel_id.to_string())
-        .print();
-    // let caption = String::from("HEllow");
-    let sent = bot
-        .send_photo(
-            teloxide::types::ChatId(channel_id),
-            teloxide::types::InputFile::file_id(best.file.id.clone()),
-        )
-        .caption(caption.clone())
-        .await?;
-    log("tg", "photo", Level::Info, "Опубликовано в канал")
-        .data("channel_id", channel_id.to_string())
-        .print();
-
-    // Log publication to SQLite
-    let msg_id = sent.id.0;
-    let file_id = Some(best.file.id.to_string());
-    db.log_post(channel_id, Some(msg_id as i64), file_id, Some(caption))
-        .await?;
+    // Показываем подпись на утверждение: публикация — по кнопке под предпросмотром.
+    // Подпись, не прошедшая проверку, тоже идёт на утверждение (кроме `!текст`)
+    let violations = check_policy(&config, &captions[0], job.note.as_ref())?;
+    if config.approval_required(false) || !violations.is_empty() {
+        let draft = NewDraft {
+            chat_id: msg.chat.id.0,
+            channel_id,
+            file_id: Some(best.file.id.to_string()),
+            path: None,
+            hash: None,
+            status: DraftStatus::Pending,
+            scheduled: false,
+            uploader_note: note.map(str::to_string),
+        };
+        drafts::propose(&bot, &db, &config, draft, &captions, Some(msg.id)).await?;
+        return Ok(());
+    }
 
-    // Дублируем опубликованный пост в чат с пользователем
-    bot.forward_message(msg.chat.id, teloxide::types::ChatId(channel_id), sent.id)
-        .await?;
+    // Публикуем в канал: без кадрирования переиспользуем file_id исходного фото,
+    // чтобы не перезагружать файл
+    let translation = translate_for(&config, &db, provider.as_ref(), channel_id, &captions[0]).await?;
+    let post = Post {
+        channel_id,
+        source: &source,
+        bytes: &bytes,
+        caption: &captions[0],
+        scheduled: false,
+        translation: translation.as_ref(),
+        sidecar: &job.sidecar,
+    };
+    let sent = publish_photo(&bot, &db, &config, post).await?;
 
-    // Дополнительное подтверждение пользователю
-    bot.send_message(
-        msg.chat.id,
-        "Пост опубликован в канал и продублирован сюда.",
-    )
-    .await?;
+    // Дублируем опубликованный пост в чат с пользователем и подтверждаем
+    notify_published(&bot, msg.chat.id, channel_id, &sent).await?;
 
     Ok(())
 }
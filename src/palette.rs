@@ -0,0 +1,100 @@
+// Локальный анализ изображения: доминирующие оттенки по уменьшенной копии.
+use anyhow::{Context, Result};
+
+/// Доля пикселей, начиная с которой оттенок попадает в палитру.
+const MIN_SHARE: f32 = 0.05;
+
+/// Названия доминирующих оттенков (не больше `max`), от самого частого.
+/// Белый (бумага) учитывается, только если других оттенков нет.
+pub fn dominant_colors(bytes: &[u8], max: usize) -> Result<Vec<&'static str>> {
+    let img = image::load_from_memory(bytes)
+        .context("не удалось декодировать изображение")?
+        .thumbnail(64, 64)
+        .to_rgb8();
+    let total = (img.width() * img.height()).max(1) as f32;
+    let mut counts: Vec<(&'static str, u32)> = Vec::new();
+    for px in img.pixels() {
+        let name = color_name(px.0);
+        match counts.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, c)) => *c += 1,
+            None => counts.push((name, 1)),
+        }
+    }
+    counts.sort_by_key(|(_, c)| std::cmp::Reverse(*c));
+    let mut names: Vec<&'static str> = counts
+        .iter()
+        .filter(|(_, c)| *c as f32 / total >= MIN_SHARE)
+        .map(|(n, _)| *n)
+        .collect();
+    if names.len() > 1 {
+        names.retain(|n| *n != "белый");
+    }
+    names.truncate(max);
+    Ok(names)
+}
+
+/// Название оттенка пикселя по HSV.
+fn color_name([r, g, b]: [u8; 3]) -> &'static str {
+    let (r, g, b) = (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let s = if max == 0.0 { 0.0 } else { delta / max };
+    if max < 0.2 {
+        return "чёрный";
+    }
+    if s < 0.15 {
+        return if max > 0.85 {
+            "белый"
+        } else {
+            "серый"
+        };
+    }
+    let h = if max == r {
+        60.0 * (((g - b) / delta).rem_euclid(6.0))
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    match h {
+        h if !(15.0..345.0).contains(&h) => "красный",
+        h if h < 40.0 => "оранжевый",
+        h if h < 65.0 => "жёлтый",
+        h if h < 160.0 => "зелёный",
+        h if h < 190.0 => "бирюзовый",
+        h if h < 220.0 => "голубой",
+        h if h < 260.0 => "синий",
+        h if h < 290.0 => "фиолетовый",
+        _ => "розовый",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn names_basic_colors() {
+        assert_eq!(color_name([220, 30, 30]), "красный");
+        assert_eq!(color_name([30, 60, 200]), "синий");
+        assert_eq!(color_name([250, 250, 248]), "белый");
+        assert_eq!(color_name([10, 10, 10]), "чёрный");
+    }
+
+    #[test]
+    fn ignores_paper_white() {
+        let mut img = RgbImage::from_pixel(40, 40, Rgb([252, 252, 250]));
+        for x in 0..20 {
+            for y in 0..40 {
+                img.put_pixel(x, y, Rgb([40, 150, 60]));
+            }
+        }
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgb8(img)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        assert_eq!(dominant_colors(&png, 3).unwrap(), vec!["зелёный"]);
+    }
+}
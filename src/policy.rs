@@ -0,0 +1,309 @@
+// Проверка подписи перед публикацией (`caption_policy`): запрещённые слова и
+// выражения, служебные фразы модели («As an AI»), латиница в русском тексте,
+// число абзацев и эмодзи. Подпись с нарушениями генерируется заново, а если и
+// это не помогло — уходит на утверждение вместо публикации.
+use anyhow::{Context, Result};
+use once_cell::sync::OnceCell;
+use regex::Regex;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::config::Config;
+
+/// Настройки проверки.
+#[derive(Debug, Deserialize, Clone)]
+pub struct CaptionPolicy {
+    /// Слова и фразы, которых не должно быть в подписи (целиком, без учёта регистра).
+    #[serde(default)]
+    pub banned_words: Vec<String>,
+    /// Регулярные выражения, которых не должно быть в подписи.
+    #[serde(default)]
+    pub banned_patterns: Vec<String>,
+    /// Фразы, выдающие ответ модели вместо подписи.
+    #[serde(default = "default_leak_phrases")]
+    pub leak_phrases: Vec<String>,
+    /// Наибольшая доля латинских букв среди всех букв (без хэштегов и ссылок).
+    #[serde(default = "default_max_latin_ratio")]
+    pub max_latin_ratio: Option<f64>,
+    #[serde(default)]
+    pub min_paragraphs: Option<usize>,
+    #[serde(default)]
+    pub max_paragraphs: Option<usize>,
+    #[serde(default)]
+    pub min_emoji: usize,
+    #[serde(default)]
+    pub max_emoji: Option<usize>,
+    /// Сколько раз генерировать заново, если все варианты нарушают правила.
+    #[serde(default = "default_retries")]
+    pub retries: usize,
+    /// Выражения, собранные при первой проверке (при старте — в [`validate`]).
+    #[serde(skip)]
+    compiled: OnceCell<Compiled>,
+}
+
+impl Default for CaptionPolicy {
+    fn default() -> Self {
+        Self {
+            banned_words: Vec::new(),
+            banned_patterns: Vec::new(),
+            leak_phrases: default_leak_phrases(),
+            max_latin_ratio: default_max_latin_ratio(),
+            min_paragraphs: None,
+            max_paragraphs: None,
+            min_emoji: 0,
+            max_emoji: None,
+            retries: default_retries(),
+            compiled: OnceCell::new(),
+        }
+    }
+}
+
+fn default_leak_phrases() -> Vec<String> {
+    [
+        "as an ai",
+        "language model",
+        "i'm sorry",
+        "i cannot",
+        "system prompt",
+        "как ии",
+        "как искусственный интеллект",
+        "языковая модель",
+        "системный промпт",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+fn default_max_latin_ratio() -> Option<f64> {
+    Some(0.3)
+}
+
+fn default_retries() -> usize {
+    1
+}
+
+/// Нарушение правил подписи.
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum Violation {
+    #[error("запрещённое слово «{0}»")]
+    BannedWord(String),
+    #[error("запрещённое выражение «{0}»")]
+    BannedPattern(String),
+    #[error("служебная фраза модели «{0}»")]
+    PromptLeak(String),
+    #[error("слишком много латиницы: {0:.0}%")]
+    Latin(f64),
+    #[error("абзацев {0}, нужно не меньше {1}")]
+    TooFewParagraphs(usize, usize),
+    #[error("абзацев {0}, нужно не больше {1}")]
+    TooManyParagraphs(usize, usize),
+    #[error("эмодзи {0}, нужно не меньше {1}")]
+    TooFewEmoji(usize, usize),
+    #[error("эмодзи {0}, нужно не больше {1}")]
+    TooManyEmoji(usize, usize),
+}
+
+/// Собранные регулярные выражения правил: фраза или выражение и его regex.
+#[derive(Debug, Clone)]
+struct Compiled {
+    banned: Vec<(String, Regex)>,
+    patterns: Vec<(String, Regex)>,
+    leaks: Vec<(String, Regex)>,
+}
+
+/// Слово или фраза целиком, без учёта регистра.
+fn phrase_regex(phrase: &str) -> Result<Regex> {
+    Regex::new(&format!(
+        r"(?i)(?:^|\W){}(?:$|\W)",
+        regex::escape(phrase.trim())
+    ))
+    .with_context(|| format!("некорректная фраза «{}»", phrase))
+}
+
+impl Compiled {
+    fn new(config: &CaptionPolicy) -> Result<Self> {
+        let phrases = |list: &[String]| -> Result<Vec<(String, Regex)>> {
+            list.iter()
+                .filter(|p| !p.trim().is_empty())
+                .map(|p| Ok((p.clone(), phrase_regex(p)?)))
+                .collect()
+        };
+        let patterns = config
+            .banned_patterns
+            .iter()
+            .map(|p| {
+                Regex::new(p)
+                    .map(|re| (p.clone(), re))
+                    .with_context(|| format!("некорректное выражение banned_patterns: {}", p))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            banned: phrases(&config.banned_words)?,
+            patterns,
+            leaks: phrases(&config.leak_phrases)?,
+        })
+    }
+}
+
+impl CaptionPolicy {
+    /// Выражения правил: собираются один раз и дальше переиспользуются.
+    fn compiled(&self) -> Result<&Compiled> {
+        self.compiled.get_or_try_init(|| Compiled::new(self))
+    }
+
+    fn check(&self, caption: &str) -> Result<Vec<Violation>> {
+        let re = self.compiled()?;
+        let mut out = Vec::new();
+        let found = |list: &[(String, Regex)]| -> Vec<String> {
+            list.iter()
+                .filter(|(_, re)| re.is_match(caption))
+                .map(|(p, _)| p.clone())
+                .collect()
+        };
+        out.extend(found(&re.banned).into_iter().map(Violation::BannedWord));
+        out.extend(
+            found(&re.patterns)
+                .into_iter()
+                .map(Violation::BannedPattern),
+        );
+        out.extend(found(&re.leaks).into_iter().map(Violation::PromptLeak));
+        if let Some(max) = self.max_latin_ratio {
+            let ratio = latin_ratio(caption);
+            if ratio > max {
+                out.push(Violation::Latin(ratio * 100.0));
+            }
+        }
+        let paragraphs = paragraphs(caption);
+        if let Some(min) = self.min_paragraphs.filter(|&m| paragraphs < m) {
+            out.push(Violation::TooFewParagraphs(paragraphs, min));
+        }
+        if let Some(max) = self.max_paragraphs.filter(|&m| paragraphs > m) {
+            out.push(Violation::TooManyParagraphs(paragraphs, max));
+        }
+        let emoji = caption.chars().filter(|&ch| is_emoji(ch)).count();
+        if emoji < self.min_emoji {
+            out.push(Violation::TooFewEmoji(emoji, self.min_emoji));
+        }
+        if let Some(max) = self.max_emoji.filter(|&m| emoji > m) {
+            out.push(Violation::TooManyEmoji(emoji, max));
+        }
+        Ok(out)
+    }
+}
+
+/// Доля латинских букв среди всех букв; хэштеги и ссылки не считаются.
+fn latin_ratio(caption: &str) -> f64 {
+    let (mut letters, mut latin) = (0usize, 0usize);
+    for word in caption.split_whitespace() {
+        if word.starts_with('#') || word.contains("://") {
+            continue;
+        }
+        for ch in word.chars().filter(|c| c.is_alphabetic()) {
+            letters += 1;
+            if ch.is_ascii_alphabetic() {
+                latin += 1;
+            }
+        }
+    }
+    if letters == 0 {
+        return 0.0;
+    }
+    latin as f64 / letters as f64
+}
+
+/// Абзацы подписи; строка из одних хэштегов абзацем не считается.
+fn paragraphs(caption: &str) -> usize {
+    caption
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .filter(|p| !p.split_whitespace().all(|w| w.starts_with('#')))
+        .count()
+}
+
+fn is_emoji(ch: char) -> bool {
+    matches!(
+        ch as u32,
+        0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B50 | 0x2B55
+    )
+}
+
+/// Проверка `caption_policy` при старте: выражения должны собираться.
+pub fn validate(config: &Config) -> Result<()> {
+    config.caption_policy.compiled().map(|_| ())
+}
+
+/// Нарушения правил в подписи (пустой список — подпись можно публиковать).
+pub fn check(config: &Config, caption: &str) -> Result<Vec<Violation>> {
+    config.caption_policy.check(caption)
+}
+
+/// Список нарушений одной строкой.
+pub fn describe(violations: &[Violation]) -> String {
+    violations
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Подсказка для повторной генерации: что было не так с прошлым ответом.
+pub fn hint(violations: &[Violation]) -> String {
+    let mut hint = format!(
+        "\n\nПрошлый ответ не прошёл проверку ({}). Исправь это в новом варианте.",
+        describe(violations)
+    );
+    if violations
+        .iter()
+        .any(|v| matches!(v, Violation::PromptLeak(_)))
+    {
+        hint.push_str(" Пиши только саму подпись, не упоминай, что ты модель или ИИ.");
+    }
+    hint
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(json: serde_json::Value) -> CaptionPolicy {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn finds_banned_words_leaks_and_latin() {
+        let config = policy(serde_json::json!({
+            "banned_words": ["дёшево"],
+            "banned_patterns": [r"\d+\s*%\s*скидк"]
+        }));
+        let p = |caption: &str| config.check(caption).unwrap();
+        assert!(p("Нежные пионы в утреннем свете.\n\n#акварель #watercolor").is_empty());
+        let v = p("Дёшево! 20% скидка. As an AI, I love it");
+        assert!(v.contains(&Violation::BannedWord("дёшево".to_string())));
+        assert!(v.iter().any(|v| matches!(v, Violation::BannedPattern(_))));
+        assert!(v.contains(&Violation::PromptLeak("as an ai".to_string())));
+        assert!(v.iter().any(|v| matches!(v, Violation::Latin(_))));
+        // Слово целиком: «дёшевый» не запрещено
+        assert!(p("Совсем не дёшевый подарок").is_empty());
+    }
+
+    #[test]
+    fn counts_paragraphs_and_emoji() {
+        let config = policy(serde_json::json!({
+            "min_paragraphs": 2, "max_emoji": 1
+        }));
+        let p = |caption: &str| config.check(caption).unwrap();
+        assert_eq!(
+            p("Один абзац 🌸🌿\n\n#акварель"),
+            vec![
+                Violation::TooFewParagraphs(1, 2),
+                Violation::TooManyEmoji(2, 1)
+            ]
+        );
+        assert!(p("Первый 🌸\n\nВторой").is_empty());
+        assert!(policy(serde_json::json!({"banned_patterns": ["("]}))
+            .compiled()
+            .is_err());
+    }
+}
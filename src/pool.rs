@@ -0,0 +1,358 @@
+// Пул провайдеров OpenAI (`caption_provider: "pool"`): несколько записей
+// `provider_pool` со своим адресом, ключом и моделью. При исчерпанной квоте,
+// лимите запросов или сбое сервиса запрос уходит следующей записи, а
+// отказавшая запись пропускается до конца паузы.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+
+use crate::ai_http::GenError;
+use crate::config::Config;
+use crate::generator::{OpenAiAuth, OpenAiProvider};
+use crate::logging::{compact, log, Level};
+use crate::provider::{BoxFuture, CaptionProvider, CaptionRequest, Generation};
+
+/// Запись пула: адрес, ключ и модель; незаданные поля берутся из `openai_*`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct PoolEntry {
+    /// Имя для логов и /usage.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub base: Option<String>,
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    pub api_key: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub auth: Option<OpenAiAuth>,
+    #[serde(default)]
+    pub api_version: Option<String>,
+}
+
+impl PoolEntry {
+    /// Конфиг для провайдера этой записи.
+    fn config(&self, cfg: &Config) -> Config {
+        let mut cfg = cfg.clone();
+        cfg.openai_api_key = Some(self.api_key.clone());
+        if let Some(base) = &self.base {
+            cfg.openai_base = base.clone();
+        }
+        if self.endpoint.is_some() {
+            cfg.openai_endpoint = self.endpoint.clone();
+        }
+        if let Some(model) = &self.model {
+            cfg.openai_model = model.clone();
+            cfg.openai_vision_model = Some(model.clone());
+        }
+        if let Some(auth) = self.auth {
+            cfg.openai_auth = auth;
+        }
+        if self.api_version.is_some() {
+            cfg.openai_api_version = self.api_version.clone();
+        }
+        cfg
+    }
+}
+
+/// Состояние записи пула.
+#[derive(Debug, Default)]
+struct Health {
+    /// До какого момента запись пропускается.
+    down_until: Option<Instant>,
+    /// Неудачных запросов подряд.
+    failures: u32,
+    last_error: Option<String>,
+}
+
+struct Member {
+    name: String,
+    provider: Box<dyn CaptionProvider>,
+    health: Mutex<Health>,
+}
+
+/// Провайдеры OpenAI с переключением между ними.
+pub struct PoolProvider {
+    members: Vec<Member>,
+    /// Запись, которая ответила последней: с неё начинается следующий запрос.
+    current: AtomicUsize,
+    /// Пауза после лимита запросов (без Retry-After) или сбоя сервиса.
+    cooldown: Duration,
+    /// Пауза после исчерпанной квоты или ошибки авторизации.
+    quota_cooldown: Duration,
+}
+
+impl PoolProvider {
+    pub fn from_config(cfg: &Config) -> Result<Self> {
+        if cfg.provider_pool.is_empty() {
+            bail!("provider_pool пуст: укажите записи пула");
+        }
+        let members = cfg
+            .provider_pool
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let provider = OpenAiProvider::from_config(&entry.config(cfg))?;
+                Ok(Member {
+                    name: entry
+                        .name
+                        .clone()
+                        .unwrap_or_else(|| format!("#{} {}", i + 1, provider.name())),
+                    provider: Box::new(provider),
+                    health: Mutex::default(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            members,
+            current: AtomicUsize::new(0),
+            cooldown: Duration::from_secs(cfg.pool_cooldown_secs),
+            quota_cooldown: Duration::from_secs(cfg.pool_quota_cooldown_secs),
+        })
+    }
+
+    /// Порядок обхода: доступные записи по кругу от текущей, затем те, что на
+    /// паузе (раньше всех освободится — первой), чтобы не остаться без ответа.
+    fn order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let start = self.current.load(Ordering::Relaxed);
+        let len = self.members.len();
+        let (mut ready, mut paused): (Vec<_>, Vec<_>) = (0..len)
+            .map(|k| (start + k) % len)
+            .map(|i| (i, self.members[i].health.lock().unwrap().down_until))
+            .partition(|(_, until)| until.is_none_or(|t| t <= now));
+        paused.sort_by_key(|(_, until)| *until);
+        ready.append(&mut paused);
+        ready.into_iter().map(|(i, _)| i).collect()
+    }
+
+    /// На сколько отложить запись после ошибки. Паузу получают только квота,
+    /// лимит запросов и сбои сервиса (5xx, таймаут, соединение); `None` — дело
+    /// не в записи (фильтр контента, ошибка запроса 4xx, ответ не по формату),
+    /// другая запись ответит так же, поэтому ошибка возвращается сразу.
+    fn pause_for(&self, err: &anyhow::Error) -> Option<Duration> {
+        match err.downcast_ref::<GenError>()? {
+            GenError::Quota { .. } | GenError::Auth { .. } => Some(self.quota_cooldown),
+            GenError::RateLimit { retry_after, .. } => Some(retry_after.unwrap_or(self.cooldown)),
+            GenError::Server { .. } => Some(self.cooldown),
+            GenError::Transport(err) if !err.is_decode() => Some(self.cooldown),
+            _ => None,
+        }
+    }
+
+    fn mark_ok(&self, i: usize) {
+        let mut health = self.members[i].health.lock().unwrap();
+        if health.failures > 0 {
+            log(
+                "caption",
+                "pool",
+                Level::Info,
+                "Провайдер пула снова отвечает",
+            )
+            .data("provider", self.members[i].name.clone())
+            .print();
+        }
+        *health = Health::default();
+        self.current.store(i, Ordering::Relaxed);
+    }
+
+    fn mark_failed(&self, i: usize, pause: Duration, err: &anyhow::Error) {
+        let mut health = self.members[i].health.lock().unwrap();
+        health.failures += 1;
+        health.down_until = Some(Instant::now() + pause);
+        health.last_error = Some(err.to_string());
+        self.current
+            .store((i + 1) % self.members.len(), Ordering::Relaxed);
+        log(
+            "caption",
+            "pool",
+            Level::Warn,
+            "Провайдер пула недоступен, переключаемся на следующий",
+        )
+        .data("provider", self.members[i].name.clone())
+        .data("pause_secs", pause.as_secs().to_string())
+        .data("failures", health.failures.to_string())
+        .data("error", err.to_string())
+        .print();
+    }
+
+    /// Выполняет запрос первой записью, которая ответит.
+    async fn run<'a, T>(
+        &'a self,
+        call: impl Fn(&'a dyn CaptionProvider) -> BoxFuture<'a, Result<T>>,
+    ) -> Result<T> {
+        let mut last_err = None;
+        for i in self.order() {
+            match call(self.members[i].provider.as_ref()).await {
+                Ok(value) => {
+                    self.mark_ok(i);
+                    return Ok(value);
+                }
+                Err(err) => match self.pause_for(&err) {
+                    None => return Err(err),
+                    Some(pause) => {
+                        self.mark_failed(i, pause, &err);
+                        last_err = Some(err);
+                    }
+                },
+            }
+        }
+        Err(last_err.unwrap_or_else(|| anyhow!("пул провайдеров пуст")))
+    }
+}
+
+impl CaptionProvider for PoolProvider {
+    fn name(&self) -> String {
+        let names = self
+            .members
+            .iter()
+            .map(|m| m.name.clone())
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("pool({})", names)
+    }
+
+    fn generate<'a>(&'a self, req: &'a CaptionRequest<'a>) -> BoxFuture<'a, Result<Generation>> {
+        Box::pin(self.run(move |p| p.generate(req)))
+    }
+
+    fn complete<'a>(&'a self, system: &'a str, user: &'a str) -> BoxFuture<'a, Result<Generation>> {
+        Box::pin(self.run(move |p| p.complete(system, user)))
+    }
+
+    fn generate_variants<'a>(
+        &'a self,
+        req: &'a CaptionRequest<'a>,
+        n: usize,
+    ) -> BoxFuture<'a, Result<Vec<Generation>>> {
+        Box::pin(self.run(move |p| p.generate_variants(req, n)))
+    }
+
+    fn health(&self) -> Vec<String> {
+        let now = Instant::now();
+        self.members
+            .iter()
+            .map(|m| {
+                let health = m.health.lock().unwrap();
+                match health.down_until.filter(|&t| t > now) {
+                    Some(until) => format!(
+                        "{}: на паузе ещё {} с, ошибок подряд {} ({})",
+                        m.name,
+                        (until - now).as_secs(),
+                        health.failures,
+                        compact(health.last_error.as_deref().unwrap_or("-"), 80)
+                    ),
+                    None if health.failures > 0 => format!(
+                        "{}: доступен после ошибок ({} подряд)",
+                        m.name, health.failures
+                    ),
+                    None => format!("{}: в работе", m.name),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use crate::stub_server::{self, StubResponse};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn rotates_on_quota_and_skips_paused_member() {
+        let (first, first_server) = stub_server::start(vec![StubResponse::json(
+            429,
+            json!({"error": {"code": "insufficient_quota", "message": "quota"}}),
+        )]);
+        let (second, second_server) = stub_server::start(vec![
+            StubResponse::json(200, json!({"choices": [{"message": {"content": "Пионы"}}]})),
+            StubResponse::json(200, json!({"choices": [{"message": {"content": "Розы"}}]})),
+        ]);
+        let pool = PoolProvider::from_config(&test_config(json!({
+            "ai_max_retries": 0,
+            "provider_pool": [
+                {"name": "main", "base": first, "api_key": "k1"},
+                {"name": "spare", "base": second, "api_key": "k2", "model": "gpt-4o-mini"}
+            ]
+        })))
+        .unwrap();
+        let req = CaptionRequest {
+            image: b"img",
+            system_prompt: "prompt",
+            params: Default::default(),
+            structured: false,
+        };
+        assert_eq!(pool.generate(&req).await.unwrap().text, "Пионы");
+        // Первая запись на паузе: второй запрос сразу уходит запасной
+        assert_eq!(pool.generate(&req).await.unwrap().text, "Розы");
+        let health = pool.health();
+        assert!(health[0].starts_with("main: на паузе"));
+        assert_eq!(health[1], "spare: в работе");
+        assert_eq!(first_server.join().unwrap().len(), 1);
+        let bodies = second_server.join().unwrap();
+        assert!(bodies[0].contains("gpt-4o-mini"));
+    }
+
+    #[tokio::test]
+    async fn request_errors_do_not_pause_members() {
+        let (base, server) = stub_server::start(vec![
+            StubResponse::json(
+                400,
+                json!({"error": {"message": "Unrecognized request argument: top_k"}}),
+            ),
+            StubResponse::json(200, json!({"unexpected": true})),
+        ]);
+        let pool = PoolProvider::from_config(&test_config(json!({
+            "ai_max_retries": 0,
+            "provider_pool": [
+                {"name": "main", "base": base, "api_key": "k1"},
+                {"name": "spare", "base": "http://127.0.0.1:9", "api_key": "k2"}
+            ]
+        })))
+        .unwrap();
+        let err = pool
+            .complete("system", "user")
+            .await
+            .expect_err("ожидается ошибка запроса");
+        assert!(matches!(
+            err.downcast_ref::<GenError>(),
+            Some(GenError::BadResponse { status: 400, .. })
+        ));
+        // Ответ не по формату тоже не повод выводить запись из ротации
+        assert!(pool.complete("system", "user").await.is_err());
+        assert_eq!(pool.health(), vec!["main: в работе", "spare: в работе"]);
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn content_filter_is_not_a_failover() {
+        let (base, server) = stub_server::start(vec![StubResponse::json(
+            200,
+            json!({"choices": [{"finish_reason": "content_filter", "message": {"content": null}}]}),
+        )]);
+        let pool = PoolProvider::from_config(&test_config(json!({
+            "ai_max_retries": 0,
+            "provider_pool": [
+                {"base": base, "api_key": "k1"},
+                {"base": "http://127.0.0.1:9", "api_key": "k2"}
+            ]
+        })))
+        .unwrap();
+        let err = pool
+            .complete("system", "user")
+            .await
+            .expect_err("ожидается ошибка фильтра");
+        assert!(matches!(
+            err.downcast_ref::<GenError>(),
+            Some(GenError::ContentFilter { .. })
+        ));
+        assert!(pool.health()[0].ends_with("в работе"));
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+// Библиотека промптов: именованные персоны из `prompts_dir` (`<имя>.txt`)
+// с подстановкой переменных {title}, {series}, {palette}, {date}, {weekday}, {season}.
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use time::{Month, OffsetDateTime, Weekday};
+
+use crate::config::Config;
+use crate::db::Db;
+use crate::logging::{log, Level};
+
+/// Значения переменных для шаблона промпта.
+#[derive(Debug, Clone)]
+pub struct PromptVars {
+    pub title: String,
+    pub series: String,
+    pub palette: Vec<String>,
+    pub date: OffsetDateTime,
+}
+
+impl PromptVars {
+    /// Подставляет переменные в шаблон; неизвестные `{...}` остаются как есть.
+    pub fn render(&self, template: &str) -> String {
+        let date = format!(
+            "{:02}.{:02}.{}",
+            self.date.day(),
+            self.date.month() as u8,
+            self.date.year()
+        );
+        template
+            .replace("{title}", &self.title)
+            .replace("{series}", &self.series)
+            .replace("{palette}", &self.palette.join(", "))
+            .replace("{date}", &date)
+            .replace("{weekday}", weekday_ru(self.date.weekday()))
+            .replace("{season}", season_ru(self.date.month()))
+    }
+}
+
+fn weekday_ru(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Monday => "понедельник",
+        Weekday::Tuesday => "вторник",
+        Weekday::Wednesday => "среда",
+        Weekday::Thursday => "четверг",
+        Weekday::Friday => "пятница",
+        Weekday::Saturday => "суббота",
+        Weekday::Sunday => "воскресенье",
+    }
+}
+
+fn season_ru(month: Month) -> &'static str {
+    match month {
+        Month::December | Month::January | Month::February => "зима",
+        Month::March | Month::April | Month::May => "весна",
+        Month::June | Month::July | Month::August => "лето",
+        _ => "осень",
+    }
+}
+
+/// Имя персоны допустимо как имя файла: буквы, цифры, `-` и `_`.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Список персон (имён шаблонов) в каталоге `prompts_dir`, по алфавиту.
+pub fn list_personas(config: &Config) -> Vec<String> {
+    let Some(dir) = config.prompts_dir.as_deref() else {
+        return Vec::new();
+    };
+    let Ok(rd) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = rd
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("txt"))
+        .filter_map(|p| p.file_stem().and_then(|s| s.to_str()).map(str::to_string))
+        .filter(|n| valid_name(n))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Читает шаблон персоны `name`.
+pub fn load_persona(config: &Config, name: &str) -> Result<String> {
+    let Some(dir) = config.prompts_dir.as_deref() else {
+        bail!("prompts_dir не задан в конфиге");
+    };
+    if !valid_name(name) {
+        bail!("некорректное имя персоны: {}", name);
+    }
+    let path = Path::new(dir).join(format!("{}.txt", name));
+    std::fs::read_to_string(&path)
+        .with_context(|| format!("не удалось прочитать шаблон {}", path.display()))
+}
+
+/// Персона для публикации: для расписания — `post_persona`, затем персона
+/// канала, затем выбранная командой /persona, затем `persona` из конфига.
+pub async fn active_persona(
+    config: &Config,
+    db: &Db,
+    channel_id: i64,
+    scheduled: bool,
+) -> Result<Option<String>> {
+    if scheduled {
+        if let Some(p) = &config.post_persona {
+            return Ok(Some(p.clone()));
+        }
+    }
+    if let Some(p) = config.channel(channel_id).persona {
+        return Ok(Some(p));
+    }
+    if let Some(p) = db.get_persona().await? {
+        return Ok(Some(p));
+    }
+    Ok(config.persona.clone())
+}
+
+/// Системный промпт персоны с подставленными переменными. Если персона не
+/// выбрана или шаблон не читается — промпт из `openai_system_prompt`/встроенный.
+pub fn system_prompt_for(config: &Config, persona: Option<&str>, vars: &PromptVars) -> String {
+    if let Some(name) = persona {
+        match load_persona(config, name) {
+            Ok(template) => {
+                log("prompts", "persona", Level::Debug, "Используется персона")
+                    .data("persona", name)
+                    .print();
+                return vars.render(&template);
+            }
+            Err(err) => {
+                log(
+                    "prompts",
+                    "persona",
+                    Level::Warn,
+                    "Шаблон персоны недоступен, используем промпт по умолчанию",
+                )
+                .data("persona", name)
+                .data("error", err.to_string())
+                .print();
+            }
+        }
+    }
+    vars.render(&crate::generator::system_prompt(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn renders_variables() {
+        let vars = PromptVars {
+            title: "Пионы".to_string(),
+            series: "Сад".to_string(),
+            palette: vec!["розовый".to_string(), "зелёный".to_string()],
+            date: datetime!(2024-12-07 10:00 UTC),
+        };
+        assert_eq!(
+            vars.render("{title} / {series} / {palette} / {date} / {weekday} / {season} / {other}"),
+            "Пионы / Сад / розовый, зелёный / 07.12.2024 / суббота / зима / {other}"
+        );
+    }
+
+    #[test]
+    fn rejects_path_like_names() {
+        assert!(valid_name("nezhny_stil-2"));
+        assert!(!valid_name("../secret"));
+        assert!(!valid_name(""));
+    }
+}
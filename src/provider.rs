@@ -0,0 +1,658 @@
+// Абстракция генератора подписей: общий трейт и реализации для локального
+// Ollama API, фиксированного шаблона и цепочки с переходом к следующему при ошибке.
+use std::future::Future;
+use std::pin::Pin;
+
+use anyhow::{anyhow, bail, Result};
+use base64::{engine::general_purpose, Engine as _};
+use futures::future::join_all;
+use serde_json::json;
+
+use crate::ai_http::{build_client, send_json, GenError, RetryPolicy};
+use crate::config::Config;
+use crate::gen_params::{for_model, GenParams};
+use crate::generator::OpenAiProvider;
+use crate::logging::{log, Level};
+use crate::pool::PoolProvider;
+use crate::structured;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Входные данные для генерации подписи.
+pub struct CaptionRequest<'a> {
+    /// Исходные байты изображения.
+    pub image: &'a [u8],
+    /// Системный промпт.
+    pub system_prompt: &'a str,
+    /// Параметры генерации персоны (поверх параметров модели).
+    pub params: GenParams,
+    /// Просить ответ JSON-объектом по схеме `structured::schema`.
+    pub structured: bool,
+}
+
+impl<'a> CaptionRequest<'a> {
+    /// Тот же запрос с другой температурой (для вариантов подписи).
+    pub fn with_temperature(&self, temperature: f32) -> CaptionRequest<'a> {
+        CaptionRequest {
+            image: self.image,
+            system_prompt: self.system_prompt,
+            params: GenParams {
+                temperature: Some(temperature),
+                ..self.params
+            },
+            structured: self.structured,
+        }
+    }
+}
+
+/// Расход токенов одного запроса к модели.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Usage {
+    /// Модель из ответа API (или из настроек, если API её не вернул).
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// Результат генерации: текст подписи и расход токенов, если провайдер его сообщает.
+#[derive(Debug, Clone)]
+pub struct Generation {
+    pub text: String,
+    pub usage: Option<Usage>,
+}
+
+impl Generation {
+    /// Подпись без учёта расхода (шаблоны и т. п.).
+    pub fn text(text: String) -> Self {
+        Self { text, usage: None }
+    }
+}
+
+/// Параметры текстовых запросов (перевод и т. п.): сдержанная температура
+/// и запас токенов на длинную подпись.
+pub const TEXT_PARAMS: GenParams = GenParams {
+    temperature: Some(0.3),
+    top_p: None,
+    max_tokens: Some(800),
+    token_param: None,
+    detail: None,
+};
+
+/// Температуры для `n` вариантов: от сдержанной к более свободной.
+pub fn variant_temperatures(n: usize) -> Vec<f32> {
+    match n {
+        0 => Vec::new(),
+        1 => vec![0.9],
+        n => (0..n)
+            .map(|i| 0.7 + 0.5 * i as f32 / (n - 1) as f32)
+            .collect(),
+    }
+}
+
+/// Источник подписей к картинам.
+pub trait CaptionProvider: Send + Sync {
+    /// Короткое имя для логов, например `openai:gpt-4o`.
+    fn name(&self) -> String;
+
+    /// Генерирует текст подписи.
+    fn generate<'a>(&'a self, req: &'a CaptionRequest<'a>) -> BoxFuture<'a, Result<Generation>>;
+
+    /// Текстовый запрос без изображения (например, перевод готовой подписи).
+    fn complete<'a>(
+        &'a self,
+        _system: &'a str,
+        _user: &'a str,
+    ) -> BoxFuture<'a, Result<Generation>> {
+        Box::pin(async move {
+            bail!(
+                "провайдер {} не поддерживает текстовые запросы",
+                self.name()
+            )
+        })
+    }
+
+    /// Генерирует до `n` вариантов подписи. По умолчанию — параллельные запросы
+    /// с разной температурой; неудачные варианты пропускаются.
+    fn generate_variants<'a>(
+        &'a self,
+        req: &'a CaptionRequest<'a>,
+        n: usize,
+    ) -> BoxFuture<'a, Result<Vec<Generation>>> {
+        Box::pin(async move {
+            let requests: Vec<CaptionRequest<'a>> = variant_temperatures(n)
+                .into_iter()
+                .map(|t| req.with_temperature(t))
+                .collect();
+            let results = join_all(requests.iter().map(|r| self.generate(r))).await;
+            collect_variants(&self.name(), results)
+        })
+    }
+
+    /// Состояние провайдера для /usage: по строке на источник (пусто, если
+    /// провайдер его не отслеживает).
+    fn health(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Собирает успешные варианты; ошибка — только если не удался ни один.
+pub fn collect_variants(
+    provider: &str,
+    results: Vec<Result<Generation>>,
+) -> Result<Vec<Generation>> {
+    let mut variants = Vec::new();
+    let mut last_err = None;
+    for res in results {
+        match res {
+            Ok(generation) => variants.push(generation),
+            Err(err) => {
+                log(
+                    "caption",
+                    "variants",
+                    Level::Warn,
+                    "Вариант не сгенерирован",
+                )
+                .data("provider", provider)
+                .data("error", err.to_string())
+                .print();
+                last_err = Some(err);
+            }
+        }
+    }
+    match last_err {
+        Some(err) if variants.is_empty() => Err(err),
+        _ => Ok(variants),
+    }
+}
+
+/// Создаёт провайдер, выбранный в `caption_provider`.
+pub fn build_provider(cfg: &Config) -> Result<Box<dyn CaptionProvider>> {
+    build_named(&cfg.caption_provider, cfg)
+}
+
+/// Создаёт провайдер по имени: `openai`, `pool`, `ollama`, `template` или `chain`.
+fn build_named(name: &str, cfg: &Config) -> Result<Box<dyn CaptionProvider>> {
+    match name {
+        "openai" => Ok(Box::new(OpenAiProvider::from_config(cfg)?)),
+        "pool" => Ok(Box::new(PoolProvider::from_config(cfg)?)),
+        "ollama" => Ok(Box::new(OllamaProvider::from_config(cfg))),
+        "template" => Ok(Box::new(TemplateProvider::from_config(cfg))),
+        "chain" => {
+            if cfg.caption_chain.is_empty() {
+                bail!("caption_chain пуст: укажите провайдеры для цепочки");
+            }
+            let providers = cfg
+                .caption_chain
+                .iter()
+                .map(|n| {
+                    if n == "chain" {
+                        bail!("цепочка не может содержать провайдер chain");
+                    }
+                    build_named(n, cfg)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Box::new(ChainProvider { providers }))
+        }
+        other => bail!("неизвестный провайдер подписей: {}", other),
+    }
+}
+
+/// Блок `options` запроса Ollama: температура, top_p и лимит токенов (`num_predict`).
+fn ollama_options(params: GenParams) -> serde_json::Value {
+    let mut options = json!({});
+    if let Some(t) = params.temperature {
+        options["temperature"] = json!(t);
+    }
+    if let Some(p) = params.top_p {
+        options["top_p"] = json!(p);
+    }
+    if let Some(n) = params.max_tokens {
+        options["num_predict"] = json!(n);
+    }
+    options
+}
+
+/// Локальная модель через Ollama-совместимый `/api/chat`.
+pub struct OllamaProvider {
+    client: reqwest::Client,
+    retry: RetryPolicy,
+    base: String,
+    model: String,
+    params: GenParams,
+}
+
+impl OllamaProvider {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            client: build_client(cfg),
+            retry: RetryPolicy::from_config(cfg),
+            base: cfg.ollama_base.trim_end_matches('/').to_string(),
+            model: cfg.ollama_model.clone(),
+            params: for_model(cfg, &cfg.ollama_model),
+        }
+    }
+
+    async fn request(&self, req: &CaptionRequest<'_>) -> Result<Generation> {
+        log("ollama", "chat", Level::Debug, "Запрос к Ollama")
+            .data("model", self.model.clone())
+            .data("base", self.base.clone())
+            .print();
+        let mut body = json!({
+            "model": self.model,
+            "stream": false,
+            "options": ollama_options(self.params.merge(req.params)),
+            "messages": [
+                {"role": "system", "content": req.system_prompt},
+                {
+                    "role": "user",
+                    "content": "Опиши работу во вложении.",
+                    "images": [general_purpose::STANDARD.encode(req.image)]
+                }
+            ]
+        });
+        if req.structured {
+            body["format"] = structured::schema();
+        }
+        self.chat(&body).await
+    }
+
+    /// Текстовый запрос без изображения.
+    async fn complete_text(&self, system: &str, user: &str) -> Result<Generation> {
+        let body = json!({
+            "model": self.model,
+            "stream": false,
+            "options": ollama_options(self.params.merge(TEXT_PARAMS)),
+            "messages": [
+                {"role": "system", "content": system},
+                {"role": "user", "content": user}
+            ]
+        });
+        self.chat(&body).await
+    }
+
+    async fn chat(&self, body: &serde_json::Value) -> Result<Generation> {
+        let url = format!("{}/api/chat", self.base);
+        let val = send_json("ollama", self.retry, || self.client.post(&url).json(body))
+            .await
+            .inspect_err(|err| {
+                log("ollama", "chat", Level::Warn, "Ошибка Ollama")
+                    .data("error", err.to_string())
+                    .print();
+            })?;
+        let content = val["message"]["content"]
+            .as_str()
+            .ok_or_else(|| GenError::BadResponse {
+                status: 200,
+                message: "ollama response missing content".to_string(),
+            })?;
+        // Ollama считает токены промпта и ответа в prompt_eval_count/eval_count
+        let usage = val["eval_count"].as_u64().map(|completion| Usage {
+            model: val["model"].as_str().unwrap_or(&self.model).to_string(),
+            prompt_tokens: val["prompt_eval_count"].as_u64().unwrap_or(0),
+            completion_tokens: completion,
+        });
+        Ok(Generation {
+            text: content.trim().to_string(),
+            usage,
+        })
+    }
+}
+
+impl CaptionProvider for OllamaProvider {
+    fn name(&self) -> String {
+        format!("ollama:{}", self.model)
+    }
+
+    fn generate<'a>(&'a self, req: &'a CaptionRequest<'a>) -> BoxFuture<'a, Result<Generation>> {
+        Box::pin(self.request(req))
+    }
+
+    fn complete<'a>(&'a self, system: &'a str, user: &'a str) -> BoxFuture<'a, Result<Generation>> {
+        Box::pin(self.complete_text(system, user))
+    }
+}
+
+/// Фиксированный текст из `caption_template`.
+pub struct TemplateProvider {
+    template: String,
+}
+
+impl TemplateProvider {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            template: cfg.caption_template.clone(),
+        }
+    }
+}
+
+impl CaptionProvider for TemplateProvider {
+    fn name(&self) -> String {
+        "template".to_string()
+    }
+
+    fn generate<'a>(&'a self, _req: &'a CaptionRequest<'a>) -> BoxFuture<'a, Result<Generation>> {
+        let text = self.template.clone();
+        Box::pin(async move { Ok(Generation::text(text)) })
+    }
+
+    /// Шаблон один, поэтому и вариант один.
+    fn generate_variants<'a>(
+        &'a self,
+        _req: &'a CaptionRequest<'a>,
+        _n: usize,
+    ) -> BoxFuture<'a, Result<Vec<Generation>>> {
+        let text = self.template.clone();
+        Box::pin(async move { Ok(vec![Generation::text(text)]) })
+    }
+}
+
+/// Цепочка провайдеров: возвращает первый успешный результат.
+pub struct ChainProvider {
+    providers: Vec<Box<dyn CaptionProvider>>,
+}
+
+impl ChainProvider {
+    /// Выполняет запрос провайдерами по очереди до первого успешного ответа.
+    async fn run<'a, T>(
+        &'a self,
+        call: impl Fn(&'a dyn CaptionProvider) -> BoxFuture<'a, Result<T>>,
+    ) -> Result<T> {
+        let mut last_err = None;
+        for p in &self.providers {
+            match call(p.as_ref()).await {
+                Ok(value) => return Ok(value),
+                Err(err) => {
+                    log(
+                        "caption",
+                        "chain",
+                        Level::Warn,
+                        "Провайдер не сработал, пробуем следующий",
+                    )
+                    .data("provider", p.name())
+                    .data("error", err.to_string())
+                    .print();
+                    last_err = Some(err);
+                }
+            }
+        }
+        Err(last_err.unwrap_or_else(|| anyhow!("цепочка провайдеров пуста")))
+    }
+}
+
+impl CaptionProvider for ChainProvider {
+    fn name(&self) -> String {
+        let names = self
+            .providers
+            .iter()
+            .map(|p| p.name())
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("chain({})", names)
+    }
+
+    fn generate<'a>(&'a self, req: &'a CaptionRequest<'a>) -> BoxFuture<'a, Result<Generation>> {
+        Box::pin(self.run(move |p| p.generate(req)))
+    }
+
+    fn complete<'a>(&'a self, system: &'a str, user: &'a str) -> BoxFuture<'a, Result<Generation>> {
+        Box::pin(self.run(move |p| p.complete(system, user)))
+    }
+
+    fn generate_variants<'a>(
+        &'a self,
+        req: &'a CaptionRequest<'a>,
+        n: usize,
+    ) -> BoxFuture<'a, Result<Vec<Generation>>> {
+        Box::pin(self.run(move |p| {
+            Box::pin(async move {
+                let variants = p.generate_variants(req, n).await?;
+                if variants.is_empty() {
+                    bail!("провайдер {} не вернул вариантов", p.name());
+                }
+                Ok(variants)
+            })
+        }))
+    }
+
+    fn health(&self) -> Vec<String> {
+        self.providers.iter().flat_map(|p| p.health()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use crate::stub_server::{self, StubResponse};
+
+    fn config(extra: serde_json::Value) -> Config {
+        let mut config = test_config(extra);
+        config
+            .openai_api_key
+            .get_or_insert_with(|| "sk-test".to_string());
+        config
+    }
+
+    fn request() -> CaptionRequest<'static> {
+        CaptionRequest {
+            image: b"not really an image",
+            system_prompt: "prompt",
+            params: GenParams::default(),
+            structured: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn openai_provider_reads_stub_response() {
+        let (base, server) = stub_server::start(vec![StubResponse::json(
+            200,
+            json!({
+                "model": "gpt-4o-2024-08-06",
+                "choices": [{"message": {"content": "Акварельный закат"}}],
+                "usage": {"prompt_tokens": 850, "completion_tokens": 120}
+            }),
+        )]);
+        let provider = build_provider(&config(json!({"openai_base": base}))).unwrap();
+        let generation = provider.generate(&request()).await.unwrap();
+        assert_eq!(generation.text, "Акварельный закат");
+        assert_eq!(
+            generation.usage,
+            Some(Usage {
+                model: "gpt-4o-2024-08-06".to_string(),
+                prompt_tokens: 850,
+                completion_tokens: 120,
+            })
+        );
+        let bodies = server.join().unwrap();
+        assert!(bodies[0].contains("image_url"));
+    }
+
+    #[tokio::test]
+    async fn openai_returns_all_choices_with_n() {
+        let (base, server) = stub_server::start(vec![StubResponse::json(
+            200,
+            json!({"choices": [
+                {"message": {"content": "Первый"}},
+                {"message": {"content": "Второй"}, "finish_reason": "content_filter"},
+                {"message": {"content": "Третий"}}
+            ]}),
+        )]);
+        let provider = build_provider(&config(json!({"openai_base": base}))).unwrap();
+        assert_eq!(
+            provider
+                .generate_variants(&request(), 3)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|g| g.text)
+                .collect::<Vec<_>>(),
+            vec!["Первый", "Третий"]
+        );
+        let bodies = server.join().unwrap();
+        assert!(bodies[0].contains("\"n\":3"));
+    }
+
+    #[tokio::test]
+    async fn openai_body_follows_model_and_persona_params() {
+        let (base, server) = stub_server::start(vec![StubResponse::json(
+            200,
+            json!({"choices": [{"message": {"content": "Пионы"}}]}),
+        )]);
+        let provider = build_provider(&config(json!({
+            "openai_base": base,
+            "model_params": {"gpt-5": {
+                "token_param": "max_completion_tokens",
+                "max_tokens": 1200,
+                "detail": "low"
+            }}
+        })))
+        .unwrap();
+        let req = CaptionRequest {
+            params: GenParams {
+                top_p: Some(0.8),
+                ..Default::default()
+            },
+            structured: true,
+            ..request()
+        };
+        provider.generate(&req).await.unwrap();
+        let body: serde_json::Value = serde_json::from_str(&server.join().unwrap()[0]).unwrap();
+        assert_eq!(body["max_completion_tokens"], 1200);
+        assert!(body.get("max_tokens").is_none());
+        assert!((body["top_p"].as_f64().unwrap() - 0.8).abs() < 1e-6);
+        assert_eq!(
+            body["messages"][1]["content"][0]["image_url"]["detail"],
+            "low"
+        );
+    }
+
+    #[tokio::test]
+    async fn openai_responses_api_body_and_output() {
+        let (base, server) = stub_server::start_full(vec![StubResponse::json(
+            200,
+            json!({
+                "model": "gpt-5",
+                "output": [
+                    {"type": "reasoning", "summary": []},
+                    {"type": "message", "content": [{"type": "output_text", "text": "Пионы у окна"}]}
+                ],
+                "usage": {"input_tokens": 900, "output_tokens": 80}
+            }),
+        )]);
+        let provider = build_provider(&config(json!({
+            "openai_base": base,
+            "openai_api": "responses",
+            "model_params": {"gpt-5": {"max_tokens": 1200, "detail": "low"}}
+        })))
+        .unwrap();
+        let req = CaptionRequest {
+            structured: true,
+            ..request()
+        };
+        let generation = provider.generate(&req).await.unwrap();
+        assert_eq!(generation.text, "Пионы у окна");
+        assert_eq!(generation.usage.unwrap().completion_tokens, 80);
+        let (head, body) = server.join().unwrap().swap_remove(0);
+        assert!(head.starts_with("POST /v1/responses "));
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["instructions"], "prompt");
+        assert_eq!(body["max_output_tokens"], 1200);
+        assert_eq!(body["input"][0]["content"][0]["type"], "input_image");
+        assert_eq!(body["input"][0]["content"][0]["detail"], "low");
+        assert_eq!(body["text"]["format"]["type"], "json_schema");
+    }
+
+    #[tokio::test]
+    async fn openai_azure_endpoint_uses_api_key_and_version() {
+        let (base, server) = stub_server::start_full(vec![StubResponse::json(
+            200,
+            json!({"choices": [{"message": {"content": "Пионы"}}]}),
+        )]);
+        let provider = build_provider(&config(json!({
+            "openai_endpoint": format!("{}/openai/deployments/art/chat/completions", base),
+            "openai_auth": "api-key",
+            "openai_api_version": "2024-10-21",
+            "openai_headers": {"X-Gateway-Team": "art"}
+        })))
+        .unwrap();
+        provider.generate(&request()).await.unwrap();
+        let (head, _) = server.join().unwrap().swap_remove(0);
+        let head = head.to_lowercase();
+        assert!(head.starts_with(
+            "post /openai/deployments/art/chat/completions?api-version=2024-10-21 "
+        ));
+        assert!(head.contains("api-key: sk-test"));
+        assert!(head.contains("x-gateway-team: art"));
+        assert!(!head.contains("authorization:"));
+    }
+
+    #[tokio::test]
+    async fn openai_complete_sends_text_only() {
+        let (base, server) = stub_server::start(vec![StubResponse::json(
+            200,
+            json!({"choices": [{"message": {"content": "Watercolor sunset"}}]}),
+        )]);
+        let provider = build_provider(&config(json!({"openai_base": base}))).unwrap();
+        let generation = provider
+            .complete("Переведи на английский.", "Акварельный закат")
+            .await
+            .unwrap();
+        assert_eq!(generation.text, "Watercolor sunset");
+        let bodies = server.join().unwrap();
+        assert!(!bodies[0].contains("image_url"));
+        assert!(bodies[0].contains("Акварельный закат"));
+    }
+
+    #[test]
+    fn variant_temperatures_spread() {
+        assert_eq!(variant_temperatures(1), vec![0.9]);
+        let t = variant_temperatures(3);
+        assert_eq!(t.len(), 3);
+        assert!((t[0] - 0.7).abs() < 1e-6 && (t[2] - 1.2).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn ollama_provider_sends_images() {
+        let (base, server) = stub_server::start(vec![StubResponse::json(
+            200,
+            json!({"message": {"role": "assistant", "content": " Пионы \n"}}),
+        )]);
+        let provider = build_provider(&config(json!({
+            "caption_provider": "ollama",
+            "ollama_base": base,
+        })))
+        .unwrap();
+        assert_eq!(provider.name(), "ollama:llava");
+        let generation = provider.generate(&request()).await.unwrap();
+        assert_eq!(generation.text, "Пионы");
+        assert_eq!(generation.usage, None);
+        let bodies = server.join().unwrap();
+        assert!(bodies[0].contains("\"images\""));
+    }
+
+    #[tokio::test]
+    async fn chain_falls_back_to_template() {
+        let (base, server) = stub_server::start(vec![StubResponse::json(
+            401,
+            json!({"error": {"message": "boom"}}),
+        )]);
+        let provider = build_provider(&config(json!({
+            "caption_provider": "chain",
+            "caption_chain": ["openai", "template"],
+            "openai_base": base,
+            "caption_template": "Запасная подпись",
+        })))
+        .unwrap();
+        assert_eq!(
+            provider.generate(&request()).await.unwrap().text,
+            "Запасная подпись"
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn unknown_provider_is_rejected() {
+        assert!(build_provider(&config(json!({"caption_provider": "nope"}))).is_err());
+        assert!(build_provider(&config(json!({"caption_provider": "chain"}))).is_err());
+    }
+}
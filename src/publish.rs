@@ -0,0 +1,302 @@
+// Публикация в канал: загрузка исходного фото, подготовка варианта
+// (кадрирование), отправка с подписью и запись в лог публикаций.
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use teloxide::prelude::*;
+use teloxide::types::{ChatId, InputFile, ReplyParameters};
+use teloxide::{ApiError, RequestError};
+
+use crate::caption::{OverflowMode, CAPTION_LIMIT, MESSAGE_LIMIT};
+use crate::captioning::sha256_hex;
+use crate::config::Config;
+use crate::crop::{apply_crop, CropSpec};
+use crate::db::{Db, PostRecord};
+use crate::footer::{footer_for, split_with_footer};
+use crate::format::{CaptionFormat, Formatted};
+use crate::logging::{log, Level};
+use crate::sidecar::Sidecar;
+use crate::translate::{Language, Translation, BILINGUAL_SEPARATOR};
+
+/// Откуда берётся фото для публикации.
+#[derive(Debug, Clone)]
+pub enum PhotoSource {
+    /// Фото, уже загруженное в Telegram.
+    FileId(String),
+    /// Файл на диске (публикации из папки).
+    Path(PathBuf),
+}
+
+impl PhotoSource {
+    fn input_file(&self) -> InputFile {
+        match self {
+            PhotoSource::FileId(id) => InputFile::file_id(id.clone().into()),
+            PhotoSource::Path(path) => InputFile::file(path.clone()),
+        }
+    }
+}
+
+/// Скачивает оригинальные байты фото из Telegram по `file_id`.
+pub async fn download_photo(bot: &Bot, config: &Config, file_id: &str) -> Result<Vec<u8>> {
+    let file = bot.get_file(file_id.to_string().into()).await?;
+    let file_url = format!(
+        "https://api.telegram.org/file/bot{}/{}",
+        config.teloxide_token, file.path
+    );
+    let bytes = reqwest::Client::new()
+        .get(file_url)
+        .send()
+        .await
+        .context("не удалось скачать изображение")?
+        .bytes()
+        .await
+        .context("не удалось прочитать байты изображения")?;
+    log("tg", "photo", Level::Debug, "Изображение скачано")
+        .data("size", bytes.len().to_string())
+        .print();
+    Ok(bytes.to_vec())
+}
+
+/// Читает байты фото из любого источника.
+pub async fn load_photo(bot: &Bot, config: &Config, source: &PhotoSource) -> Result<Vec<u8>> {
+    match source {
+        PhotoSource::FileId(id) => download_photo(bot, config, id).await,
+        PhotoSource::Path(path) => tokio::fs::read(path)
+            .await
+            .with_context(|| format!("не удалось прочитать файл {}", path.display())),
+    }
+}
+
+/// Что и куда публикуем.
+pub struct Post<'a> {
+    pub channel_id: i64,
+    pub source: &'a PhotoSource,
+    /// Байты исходного фото (для кадрирования).
+    pub bytes: &'a [u8],
+    pub caption: &'a str,
+    /// Публикация по расписанию: влияет на выбор варианта кадрирования.
+    pub scheduled: bool,
+    /// Перевод подписи для канала на другом языке.
+    pub translation: Option<&'a Translation>,
+    /// Данные о работе для слотов подвала.
+    pub sidecar: &'a Sidecar,
+}
+
+/// Публикует фото с подписью в канал и пишет запись в `posts`.
+/// Подпись (и перевод, если он есть) оформляется в формате `caption_format` и
+/// подгоняется под лимит Telegram за вычетом подвала канала; не поместившийся
+/// хвост по настройке `caption_overflow` отбрасывается или уходит отдельным
+/// сообщением. В `posts` сохраняется подпись без подвала.
+pub async fn publish_photo(bot: &Bot, db: &Db, config: &Config, post: Post<'_>) -> Result<Message> {
+    let Post {
+        channel_id,
+        source,
+        bytes,
+        caption,
+        scheduled,
+        translation,
+        sidecar,
+    } = post;
+    log("tg", "publish", Level::Info, "Публикация в канал")
+        .data("channel_id", channel_id.to_string())
+        .print();
+    let photo = prepare_photo(
+        bytes,
+        config.crop_for(channel_id, scheduled),
+        source.input_file(),
+    )
+    .await;
+    let doc = formatted_caption(caption, config.caption_format);
+    let body = match translation {
+        Some(t) => format!("{}\n{}", caption, t.text),
+        None => caption.to_string(),
+    };
+    let footer = footer_for(config, channel_id, sidecar, &body)
+        .map(|f| formatted_caption(&f, config.caption_format));
+    let (head, overflow) =
+        split_with_footer(footer.as_ref(), CAPTION_LIMIT, |limit| match translation {
+            None => doc.split(limit),
+            Some(t) => {
+                let translated = formatted_caption(&t.text, config.caption_format);
+                match t.layout {
+                    Language::En => translated.split(limit),
+                    _ => Formatted::split_pair(&doc, &translated, BILINGUAL_SEPARATOR, limit),
+                }
+            }
+        });
+    let format = config.caption_format;
+    let mut req = bot
+        .send_photo(ChatId(channel_id), photo.clone())
+        .caption(head.render(format));
+    if let Some(mode) = format.parse_mode() {
+        req = req.parse_mode(mode);
+    }
+    let sent = match req.await {
+        Err(err) if is_markup_error(&err) => {
+            log(
+                "tg",
+                "publish",
+                Level::Warn,
+                "Telegram отклонил разметку, публикуем обычным текстом",
+            )
+            .data("error", err.to_string())
+            .print();
+            bot.send_photo(ChatId(channel_id), photo)
+                .caption(head.render(CaptionFormat::Plain))
+                .await?
+        }
+        res => res?,
+    };
+    log("tg", "publish", Level::Info, "Опубликовано в канал")
+        .data("channel_id", channel_id.to_string())
+        .data("message_id", sent.id.0.to_string())
+        .print();
+    if let Some(rest) = overflow {
+        send_overflow(bot, config, channel_id, &sent, &rest).await?;
+    }
+
+    // Telegram file_id итогового фото (для загрузки с диска — новый, иначе исходный)
+    let file_id = sent
+        .photo()
+        .and_then(|v| v.last())
+        .map(|p| p.file.id.to_string())
+        .or_else(|| match source {
+            PhotoSource::FileId(id) => Some(id.clone()),
+            PhotoSource::Path(_) => None,
+        });
+    let meta = db.get_caption_meta(&sha256_hex(caption.as_bytes())).await?;
+    let (title, description) = meta.map(|m| (m.title, m.description)).unzip();
+    db.log_post(PostRecord {
+        channel_id,
+        message_id: Some(sent.id.0 as i64),
+        file_id,
+        caption: Some(caption.to_string()),
+        translation: translation.map(|t| (t.text.clone(), t.lang.to_string())),
+        title,
+        description,
+    })
+    .await?;
+    Ok(sent)
+}
+
+/// Отправляет не поместившийся в подпись текст согласно `mode`.
+async fn send_overflow(
+    bot: &Bot,
+    config: &Config,
+    channel_id: i64,
+    sent: &Message,
+    rest: &Formatted,
+) -> Result<()> {
+    let mode = config.caption_overflow;
+    log(
+        "tg",
+        "publish",
+        Level::Info,
+        "Подпись не поместилась в лимит",
+    )
+    .data("mode", format!("{:?}", mode))
+    .data("overflow_len", rest.visible().chars().count().to_string())
+    .print();
+    if mode == OverflowMode::Drop {
+        return Ok(());
+    }
+    for part in rest.split_all(MESSAGE_LIMIT) {
+        let mut req = bot.send_message(ChatId(channel_id), part.render(config.caption_format));
+        if let Some(mode) = config.caption_format.parse_mode() {
+            req = req.parse_mode(mode);
+        }
+        if mode == OverflowMode::Reply {
+            req = req.reply_parameters(ReplyParameters::new(sent.id));
+        }
+        if let Err(err) = req.await {
+            if !is_markup_error(&err) {
+                return Err(err.into());
+            }
+            let mut req = bot.send_message(ChatId(channel_id), part.render(CaptionFormat::Plain));
+            if mode == OverflowMode::Reply {
+                req = req.reply_parameters(ReplyParameters::new(sent.id));
+            }
+            req.await?;
+        }
+    }
+    Ok(())
+}
+
+/// Разбирает разметку подписи; для `plain` и при ошибках разметки — обычный текст.
+fn formatted_caption(caption: &str, format: CaptionFormat) -> Formatted {
+    if format == CaptionFormat::Plain {
+        return Formatted::parse_or_plain(caption).0;
+    }
+    let (doc, err) = Formatted::parse_or_plain(caption);
+    if let Some(err) = err {
+        log(
+            "tg",
+            "publish",
+            Level::Warn,
+            "Некорректная разметка подписи, публикуем обычным текстом",
+        )
+        .data("error", err.to_string())
+        .print();
+    }
+    doc
+}
+
+/// Telegram не смог разобрать разметку сообщения.
+fn is_markup_error(err: &RequestError) -> bool {
+    matches!(err, RequestError::Api(ApiError::CantParseEntities(_)))
+}
+
+/// Дублирует опубликованный пост в чат `chat_id` и отправляет подтверждение.
+pub async fn notify_published(
+    bot: &Bot,
+    chat_id: ChatId,
+    channel_id: i64,
+    sent: &Message,
+) -> Result<()> {
+    bot.forward_message(chat_id, ChatId(channel_id), sent.id)
+        .await?;
+    bot.send_message(chat_id, "Пост опубликован в канал и продублирован сюда.")
+        .await?;
+    Ok(())
+}
+
+/// Готовит фото к отправке: если для канала задан вариант кадрирования —
+/// возвращает обработанные байты, иначе (или при ошибке обработки) — `original`.
+/// Декодирование и кодирование JPEG идут в отдельном потоке, не занимая рантайм.
+async fn prepare_photo(bytes: &[u8], spec: Option<CropSpec>, original: InputFile) -> InputFile {
+    let Some(spec) = spec else {
+        return original;
+    };
+    let bytes = bytes.to_vec();
+    let result = tokio::task::spawn_blocking(move || apply_crop(&bytes, &spec))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|r| r);
+    match result {
+        Ok(Some(out)) => {
+            log(
+                "photo",
+                "crop",
+                Level::Debug,
+                "Подготовлен кадрированный вариант",
+            )
+            .data("ratio", format!("{:?}", spec.ratio))
+            .data("mode", format!("{:?}", spec.mode))
+            .data("size", out.len().to_string())
+            .print();
+            InputFile::memory(out).file_name("photo.jpg")
+        }
+        Ok(None) => original,
+        Err(err) => {
+            log(
+                "photo",
+                "crop",
+                Level::Warn,
+                "Не удалось кадрировать изображение, отправляем оригинал",
+            )
+            .data("error", err.to_string())
+            .print();
+            original
+        }
+    }
+}
@@ -0,0 +1,161 @@
+// Проверка на повторы: новая подпись сравнивается с последними подписями из
+// `posts` по шинглам (последовательностям из трёх слов) и по началу истории.
+use std::collections::HashSet;
+
+use anyhow::Result;
+
+use crate::config::Config;
+use crate::db::{Db, StyleExample};
+
+/// Длина шингла в словах.
+const SHINGLE: usize = 3;
+
+/// Сколько первых слов сравнивается как начало истории.
+const OPENING_WORDS: usize = 5;
+
+/// Насколько подпись похожа на самую близкую из прошлых.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Similarity {
+    /// Коэффициент Жаккара по шинглам (0 — ничего общего, 1 — совпадение).
+    pub score: f64,
+    /// Id публикации, на которую подпись похожа больше всего.
+    pub post_id: Option<i64>,
+    /// Начало подписи совпадает с началом одной из прошлых.
+    pub same_opening: bool,
+    /// Начало подписи (для подсказки модели).
+    pub opening: String,
+}
+
+impl Similarity {
+    /// Подпись считается повтором: сходство не ниже `repetition_threshold`
+    /// или то же начало истории.
+    pub fn is_repeat(&self, config: &Config) -> bool {
+        self.same_opening || self.score >= config.repetition_threshold
+    }
+}
+
+/// Слова подписи строчными, без хэштегов, разметки и знаков препинания.
+fn words(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .filter(|w| !w.starts_with('#'))
+        .map(|w| {
+            w.chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+fn shingles(words: &[String]) -> HashSet<&[String]> {
+    words.windows(SHINGLE).collect()
+}
+
+fn jaccard(a: &HashSet<&[String]>, b: &HashSet<&[String]>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// Первые слова подписи — начало истории.
+fn opening(words: &[String]) -> &[String] {
+    &words[..words.len().min(OPENING_WORDS)]
+}
+
+/// Сравнивает подпись с прошлыми.
+pub fn compare(caption: &str, recent: &[StyleExample]) -> Similarity {
+    let new_words = words(caption);
+    let new_shingles = shingles(&new_words);
+    let new_opening = opening(&new_words);
+    let mut best = Similarity {
+        score: 0.0,
+        post_id: None,
+        same_opening: false,
+        opening: new_opening.join(" "),
+    };
+    for post in recent {
+        let old_words = words(&post.caption);
+        let score = jaccard(&new_shingles, &shingles(&old_words));
+        let same_opening = new_opening.len() == OPENING_WORDS && opening(&old_words) == new_opening;
+        if same_opening {
+            best.same_opening = true;
+        }
+        if best.post_id.is_none() || score > best.score {
+            best.score = score;
+            best.post_id = Some(post.id);
+        }
+    }
+    best
+}
+
+/// Последние `repetition_window` подписей для сравнения.
+pub async fn recent(config: &Config, db: &Db) -> Result<Vec<StyleExample>> {
+    if config.repetition_window == 0 {
+        return Ok(Vec::new());
+    }
+    db.recent_posts(config.repetition_window).await
+}
+
+/// Подсказка для повторной генерации: не повторять прошлые обороты и начала.
+pub fn hint(repeats: &[&Similarity]) -> String {
+    let mut hint = String::from(
+        "\n\nНе повторяй обороты и начала прошлых подписей: начни историю по-новому \
+         и выбери другие образы.",
+    );
+    let openings: Vec<String> = repeats
+        .iter()
+        .filter(|s| !s.opening.is_empty())
+        .map(|s| format!("«{}…»", s.opening))
+        .collect();
+    if !openings.is_empty() {
+        hint.push_str(&format!(" Не начинай так: {}.", openings.join(", ")));
+    }
+    hint
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(id: i64, caption: &str) -> StyleExample {
+        StyleExample {
+            id,
+            caption: caption.to_string(),
+            rating: None,
+            pinned: false,
+            excluded: false,
+        }
+    }
+
+    #[test]
+    fn detects_same_opening_and_overlap() {
+        let recent = vec![
+            post(
+                1,
+                "Однажды утром в тихом саду распустились пионы. Роса блестела.",
+            ),
+            post(2, "Зимний вечер, синие тени на снегу.\n\n#акварель"),
+        ];
+        let s = compare(
+            "Однажды утром в тихом саду я увидела синие тени на снегу. #акварель",
+            &recent,
+        );
+        assert!(s.same_opening);
+        assert_eq!(s.opening, "однажды утром в тихом саду");
+        assert!(s.score > 0.0);
+        let fresh = compare("Лавандовое поле на закате, тёплый ветер.", &recent);
+        assert!(!fresh.same_opening);
+        assert_eq!(fresh.score, 0.0);
+    }
+
+    #[test]
+    fn identical_text_scores_one() {
+        let recent = vec![post(7, "Пионы в вазе у окна, утренний свет.")];
+        let s = compare("Пионы в вазе у окна, утренний свет!", &recent);
+        assert_eq!(s.score, 1.0);
+        assert_eq!(s.post_id, Some(7));
+    }
+}
@@ -0,0 +1,51 @@
+// Сопроводительные данные к файлу из папки: `<имя>.json` рядом с изображением,
+// например `sunset.json` для `sunset.jpg`.
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::logging::{log, Level};
+
+/// Данные о работе, которые художница может положить рядом с файлом.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Sidecar {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub series: Option<String>,
+    /// Цена для слота `{price}` подвала.
+    #[serde(default)]
+    pub price: Option<String>,
+    /// Хэштеги, которые художница хочет видеть под постом (сверяются со словарём).
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Путь к сопроводительному файлу для изображения.
+fn sidecar_path(image: &Path) -> PathBuf {
+    image.with_extension("json")
+}
+
+/// Читает сопроводительный файл; если его нет или он некорректен — пустые данные.
+pub async fn load_sidecar(image: &Path) -> Sidecar {
+    let path = sidecar_path(image);
+    let raw = match tokio::fs::read_to_string(&path).await {
+        Ok(raw) => raw,
+        Err(_) => return Sidecar::default(),
+    };
+    match serde_json::from_str(&raw) {
+        Ok(s) => s,
+        Err(err) => {
+            log(
+                "poster",
+                "sidecar",
+                Level::Warn,
+                "Некорректный JSON рядом с файлом",
+            )
+            .data("file", path.display().to_string())
+            .data("error", err.to_string())
+            .print();
+            Sidecar::default()
+        }
+    }
+}
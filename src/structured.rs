@@ -0,0 +1,166 @@
+// Структурированный ответ модели (`structured_output`): JSON по схеме с
+// названием, абзацами истории, хэштегами, описанием изображения и ценой,
+// из которого подпись собирается по шаблону `caption_layout`.
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::db::CaptionMeta;
+
+/// Шаблон подписи по умолчанию.
+pub const DEFAULT_LAYOUT: &str = "**{title}**\n\n{story}\n\n{hashtags}";
+
+/// Подсказка в системный промпт: модели без поддержки схемы тоже отвечают JSON.
+pub const PROMPT_HINT: &str = "
+Ответ верни JSON-объектом: title — название работы, story — абзацы истории \
+(массив строк), hashtags — хэштеги без #, alt_text — описание изображения для \
+незрячих читателей, price_range — {min, max, currency} или null.";
+
+/// Ответ модели по схеме.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct StructuredCaption {
+    pub title: String,
+    pub story: Vec<String>,
+    pub hashtags: Vec<String>,
+    pub alt_text: String,
+    pub price_range: Option<PriceRange>,
+}
+
+/// Рекомендуемый диапазон цены.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PriceRange {
+    pub min: f64,
+    pub max: f64,
+    pub currency: String,
+}
+
+/// JSON-схема ответа (в строгом режиме OpenAI все поля обязательны).
+pub fn schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["title", "story", "hashtags", "alt_text", "price_range"],
+        "properties": {
+            "title": {"type": "string"},
+            "story": {"type": "array", "items": {"type": "string"}},
+            "hashtags": {"type": "array", "items": {"type": "string"}},
+            "alt_text": {"type": "string"},
+            "price_range": {
+                "type": ["object", "null"],
+                "additionalProperties": false,
+                "required": ["min", "max", "currency"],
+                "properties": {
+                    "min": {"type": "number"},
+                    "max": {"type": "number"},
+                    "currency": {"type": "string"}
+                }
+            }
+        }
+    })
+}
+
+/// Разбирает и проверяет ответ модели.
+pub fn parse(text: &str) -> Result<StructuredCaption> {
+    let caption: StructuredCaption =
+        serde_json::from_str(text.trim()).context("ответ не соответствует схеме")?;
+    if caption.title.trim().is_empty() {
+        bail!("пустое название");
+    }
+    if caption.story.iter().all(|p| p.trim().is_empty()) {
+        bail!("пустая история");
+    }
+    if let Some(p) = &caption.price_range {
+        if p.min < 0.0 || p.min > p.max {
+            bail!("некорректный диапазон цены {}–{}", p.min, p.max);
+        }
+    }
+    Ok(caption)
+}
+
+impl StructuredCaption {
+    /// Собирает подпись по шаблону с переменными {title}, {story}, {hashtags},
+    /// {alt_text}, {price}; пустые части не оставляют лишних пустых строк.
+    pub fn render(&self, layout: &str) -> String {
+        let story = self
+            .story
+            .iter()
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let hashtags = self
+            .hashtags
+            .iter()
+            .map(|t| format!("#{}", t.trim().trim_start_matches('#')))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let price = self
+            .price_range
+            .as_ref()
+            .map(|p| format!("{}–{} {}", p.min, p.max, p.currency))
+            .unwrap_or_default();
+        let text = layout
+            .replace("{title}", self.title.trim())
+            .replace("{story}", &story)
+            .replace("{hashtags}", &hashtags)
+            .replace("{alt_text}", self.alt_text.trim())
+            .replace("{price}", &price);
+        let mut out = String::new();
+        for block in text.split("\n\n").map(str::trim).filter(|b| !b.is_empty()) {
+            if !out.is_empty() {
+                out.push_str("\n\n");
+            }
+            out.push_str(block);
+        }
+        out
+    }
+
+    /// Поля, которые сохраняются отдельно от текста подписи.
+    pub fn meta(&self) -> CaptionMeta {
+        CaptionMeta {
+            title: self.title.trim().to_string(),
+            description: self.alt_text.trim().to_string(),
+            price_min: self.price_range.as_ref().map(|p| p.min),
+            price_max: self.price_range.as_ref().map(|p| p.max),
+            currency: self.price_range.as_ref().map(|p| p.currency.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_renders_layout() {
+        let caption = parse(
+            r##"{"title": "Пионы", "story": ["Утро в саду.", " ", "Роса на лепестках."],
+                "hashtags": ["акварель", "#цветы"], "alt_text": "Розовые пионы в вазе",
+                "price_range": null}"##,
+        )
+        .unwrap();
+        assert_eq!(
+            caption.render(DEFAULT_LAYOUT),
+            "**Пионы**\n\nУтро в саду.\n\nРоса на лепестках.\n\n#акварель #цветы"
+        );
+        assert_eq!(
+            caption.render("{title}\n\n{price}\n\n{alt_text}"),
+            "Пионы\n\nРозовые пионы в вазе"
+        );
+        assert_eq!(caption.meta().description, "Розовые пионы в вазе");
+    }
+
+    #[test]
+    fn rejects_invalid_answers() {
+        assert!(parse("Просто текст").is_err());
+        assert!(parse(r#"{"title": "", "story": ["a"], "hashtags": [], "alt_text": "", "price_range": null}"#).is_err());
+        assert!(parse(
+            r#"{"title": "a", "story": ["b"], "hashtags": [], "alt_text": "",
+                "price_range": {"min": 300, "max": 100, "currency": "EUR"}}"#
+        )
+        .is_err());
+        assert!(parse(r#"{"title": "a", "story": ["b"], "hashtags": [], "alt_text": "", "price_range": null, "extra": 1}"#).is_err());
+    }
+}
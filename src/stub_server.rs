@@ -0,0 +1,90 @@
+// Минимальный HTTP-сервер для тестов: отдаёт заранее заданные ответы по одному
+// на соединение и возвращает тела полученных запросов.
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread::JoinHandle;
+
+/// Заготовленный ответ: статус, дополнительные заголовки и JSON-тело.
+pub struct StubResponse {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: String,
+}
+
+impl StubResponse {
+    pub fn json(status: u16, body: serde_json::Value) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.to_string(),
+        }
+    }
+
+    pub fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+}
+
+/// Запускает сервер на свободном порту. Возвращает базовый URL и поток,
+/// который после отдачи всех ответов вернёт тела запросов (в порядке поступления).
+pub fn start(responses: Vec<StubResponse>) -> (String, JoinHandle<Vec<String>>) {
+    let (base, inner) = start_full(responses);
+    let handle = std::thread::spawn(move || {
+        inner
+            .join()
+            .unwrap()
+            .into_iter()
+            .map(|(_, body)| body)
+            .collect()
+    });
+    (base, handle)
+}
+
+/// То же, что [`start`], но поток возвращает и заголовок каждого запроса
+/// (строку запроса и заголовки, по одному на строку).
+pub fn start_full(responses: Vec<StubResponse>) -> (String, JoinHandle<Vec<(String, String)>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind stub server");
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let mut requests = Vec::new();
+        for resp in responses {
+            let (mut stream, _) = listener.accept().expect("accept");
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0usize;
+            let mut request_head = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                request_head.push_str(line);
+                request_head.push('\n');
+                if let Some((k, v)) = line.split_once(':') {
+                    if k.eq_ignore_ascii_case("content-length") {
+                        content_length = v.trim().parse().unwrap_or(0);
+                    }
+                }
+            }
+            let mut body = vec![0u8; content_length];
+            reader.read_exact(&mut body).unwrap();
+            requests.push((request_head, String::from_utf8_lossy(&body).into_owned()));
+
+            let mut head = format!(
+                "HTTP/1.1 {} STUB\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+                resp.status,
+                resp.body.len()
+            );
+            for (k, v) in &resp.headers {
+                head.push_str(&format!("{}: {}\r\n", k, v));
+            }
+            head.push_str("\r\n");
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(resp.body.as_bytes()).unwrap();
+        }
+        requests
+    });
+    (base, handle)
+}
@@ -0,0 +1,116 @@
+// Двуязычные подписи: язык канала (`ru`, `en`, `ru+en`) и перевод утверждённой
+// подписи моделью, без сочинения новой истории.
+use std::time::Instant;
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+use crate::captioning::sha256_hex;
+use crate::config::Config;
+use crate::db::Db;
+use crate::logging::{compact, log, Level};
+use crate::provider::CaptionProvider;
+use crate::usage;
+
+/// Разделитель между подписью и переводом в двуязычном посте.
+pub const BILINGUAL_SEPARATOR: &str = "\n\n— EN —\n";
+
+/// Язык публикаций канала.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    /// Только русская подпись.
+    #[default]
+    #[serde(rename = "ru")]
+    Ru,
+    /// Только перевод на английский.
+    #[serde(rename = "en")]
+    En,
+    /// Русская подпись и под ней перевод.
+    #[serde(rename = "ru+en")]
+    RuEn,
+}
+
+/// Перевод подписи и то, как его показать в посте.
+#[derive(Debug, Clone)]
+pub struct Translation {
+    /// Код языка перевода.
+    pub lang: &'static str,
+    pub text: String,
+    /// `En` — публикуется только перевод, `RuEn` — подпись и перевод.
+    pub layout: Language,
+}
+
+const TRANSLATE_PROMPT: &str = "Переведи подпись к посту о картине на английский язык. \
+Переводи близко к тексту: ничего не добавляй и не придумывай, сохрани абзацы, эмодзи, \
+хэштеги и разметку (**жирный**, *курсив*, [текст](ссылка)). Верни только перевод.";
+
+/// Переводит подпись для канала `channel_id`, если его язык не `ru`.
+/// Переводы кэшируются так же, как подписи. Если месячный бюджет исчерпан,
+/// канал получает русскую подпись без перевода (и `ru+en`, и `en`), чтобы
+/// публикация не срывалась.
+pub async fn translate_for(
+    config: &Config,
+    db: &Db,
+    provider: &dyn CaptionProvider,
+    channel_id: i64,
+    caption: &str,
+) -> Result<Option<Translation>> {
+    let layout = config.language_for(channel_id);
+    if layout == Language::Ru {
+        return Ok(None);
+    }
+    let translation = |text: String| Translation {
+        lang: "en",
+        text,
+        layout,
+    };
+    let (text_hash, prompt_hash, model) = (
+        sha256_hex(caption.as_bytes()),
+        sha256_hex(TRANSLATE_PROMPT.as_bytes()),
+        provider.name(),
+    );
+    if config.caption_cache {
+        if let Some(cached) = db
+            .get_cached_captions(&text_hash, &prompt_hash, &model)
+            .await?
+            .and_then(|c| c.into_iter().next())
+        {
+            return Ok(Some(translation(cached)));
+        }
+    }
+    if usage::budget_exhausted(config, db).await? {
+        log(
+            "ai",
+            "translate",
+            Level::Warn,
+            "Месячный бюджет исчерпан, публикуем без перевода",
+        )
+        .data("channel_id", channel_id.to_string())
+        .print();
+        return Ok(None);
+    }
+    let started = Instant::now();
+    let generation = provider.complete(TRANSLATE_PROMPT, caption).await?;
+    if let Some(used) = &generation.usage {
+        usage::record(config, db, used, started.elapsed().as_millis() as u64).await?;
+    }
+    let text = generation.text.trim().to_string();
+    if text.is_empty() {
+        bail!("провайдер {} вернул пустой перевод", model);
+    }
+    log("ai", "translate", Level::Info, "Подпись переведена")
+        .data("provider", model.clone())
+        .data("channel_id", channel_id.to_string())
+        .data("result", compact(&text, 160))
+        .print();
+    if config.caption_cache {
+        db.put_cached_captions(
+            &text_hash,
+            &prompt_hash,
+            &model,
+            std::slice::from_ref(&text),
+        )
+        .await?;
+    }
+    Ok(Some(translation(text)))
+}
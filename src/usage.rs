@@ -0,0 +1,153 @@
+// Учёт расхода AI: токены, задержка и оценка стоимости каждой генерации,
+// месячный бюджет и отчёт для команды /usage.
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::Deserialize;
+use time::{Date, OffsetDateTime, Time};
+
+use crate::config::Config;
+use crate::db::{Db, UsageRecord};
+use crate::gen_params::by_model;
+use crate::logging::{log, Level};
+use crate::provider::{CaptionProvider, Usage};
+
+/// Цена модели в долларах за миллион токенов.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    /// Токены промпта (включая изображение).
+    pub input: f64,
+    /// Токены ответа.
+    pub output: f64,
+}
+
+/// Оценка стоимости запроса; для моделей без цены — 0.
+pub fn cost_usd(prices: &HashMap<String, ModelPrice>, usage: &Usage) -> f64 {
+    by_model(prices, &usage.model)
+        .map(|p| {
+            (usage.prompt_tokens as f64 * p.input + usage.completion_tokens as f64 * p.output)
+                / 1_000_000.0
+        })
+        .unwrap_or(0.0)
+}
+
+/// Записывает расход одного обращения к провайдеру.
+pub async fn record(config: &Config, db: &Db, usage: &Usage, latency_ms: u64) -> Result<()> {
+    let cost = cost_usd(&config.model_prices, usage);
+    if cost == 0.0 && usage.prompt_tokens + usage.completion_tokens > 0 {
+        log(
+            "ai",
+            "usage",
+            Level::Debug,
+            "Цена модели не задана в model_prices",
+        )
+        .data("model", usage.model.clone())
+        .print();
+    }
+    db.record_usage(UsageRecord {
+        model: usage.model.clone(),
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens: usage.completion_tokens,
+        latency_ms,
+        cost_usd: cost,
+    })
+    .await
+}
+
+/// Начало текущего дня и месяца по локальному времени (unix-время).
+fn period_starts(now: OffsetDateTime) -> (i64, i64) {
+    let day = now.replace_time(Time::MIDNIGHT);
+    let month = Date::from_calendar_date(now.year(), now.month(), 1)
+        .map(|d| day.replace_date(d))
+        .unwrap_or(day);
+    (day.unix_timestamp(), month.unix_timestamp())
+}
+
+fn now() -> OffsetDateTime {
+    OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc())
+}
+
+/// Исчерпан ли `monthly_budget_usd` в текущем месяце.
+pub async fn budget_exhausted(config: &Config, db: &Db) -> Result<bool> {
+    let Some(budget) = config.monthly_budget_usd else {
+        return Ok(false);
+    };
+    let (_, month) = period_starts(now());
+    let spent = db.usage_totals(month).await?.cost_usd;
+    Ok(spent >= budget)
+}
+
+/// Текст отчёта для /usage: итоги за сегодня и за месяц, остаток бюджета.
+pub async fn report(config: &Config, db: &Db, provider: &dyn CaptionProvider) -> Result<String> {
+    let (day, month) = period_starts(now());
+    let mut out = String::new();
+    for (title, since) in [("Сегодня", day), ("С начала месяца", month)] {
+        let t = db.usage_totals(since).await?;
+        out.push_str(&format!(
+            "{}: запросов {}, токенов {} + {}, ≈ ${:.4}\n",
+            title, t.requests, t.prompt_tokens, t.completion_tokens, t.cost_usd
+        ));
+    }
+    if let Some(budget) = config.monthly_budget_usd {
+        let spent = db.usage_totals(month).await?.cost_usd;
+        out.push_str(&format!(
+            "Бюджет на месяц: ${:.2}, осталось ${:.2}",
+            budget,
+            (budget - spent).max(0.0)
+        ));
+        if spent >= budget {
+            out.push_str(" — исчерпан, используются запасные подписи");
+        }
+    } else {
+        out.push_str("Бюджет на месяц не задан (monthly_budget_usd).");
+    }
+    let health = provider.health();
+    if !health.is_empty() {
+        out.push_str("\n\nПровайдеры:");
+        for line in health {
+            out.push_str(&format!("\n{}", line));
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn cost_uses_longest_prefix() {
+        let prices = HashMap::from([
+            (
+                "gpt-4o".to_string(),
+                ModelPrice {
+                    input: 2.5,
+                    output: 10.0,
+                },
+            ),
+            (
+                "gpt-4o-mini".to_string(),
+                ModelPrice {
+                    input: 0.15,
+                    output: 0.6,
+                },
+            ),
+        ]);
+        let usage = |model: &str| Usage {
+            model: model.to_string(),
+            prompt_tokens: 1_000_000,
+            completion_tokens: 100_000,
+        };
+        assert_eq!(cost_usd(&prices, &usage("gpt-4o-2024-08-06")), 3.5);
+        assert!((cost_usd(&prices, &usage("gpt-4o-mini-2024-07-18")) - 0.21).abs() < 1e-9);
+        assert_eq!(cost_usd(&prices, &usage("llava")), 0.0);
+    }
+
+    #[test]
+    fn periods_start_at_local_midnight() {
+        let (day, month) = period_starts(datetime!(2024-03-15 13:45 +03:00));
+        assert_eq!(day, datetime!(2024-03-15 00:00 +03:00).unix_timestamp());
+        assert_eq!(month, datetime!(2024-03-01 00:00 +03:00).unix_timestamp());
+    }
+}
@@ -0,0 +1,88 @@
+// Подготовка изображения для Vision-запроса: уменьшение до `vision_max_edge`
+// по длинной стороне и JPEG, чтобы не отправлять в API многомегабайтные сканы.
+// Публикуется по-прежнему исходный файл.
+use anyhow::{Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+
+use crate::config::Config;
+use crate::logging::{log, Level};
+
+/// Уменьшенная JPEG-копия с длинной стороной не больше `max_edge`, или `None`,
+/// если она не меньше исходных байтов.
+fn downscale(bytes: &[u8], max_edge: u32, quality: u8) -> Result<Option<Vec<u8>>> {
+    let img = image::load_from_memory(bytes).context("не удалось декодировать изображение")?;
+    let img = if img.width().max(img.height()) > max_edge {
+        img.resize(max_edge, max_edge, FilterType::Lanczos3)
+    } else {
+        img
+    };
+    let mut buf = Vec::new();
+    JpegEncoder::new_with_quality(&mut buf, quality.clamp(1, 100))
+        .encode_image(&img.to_rgb8())
+        .context("не удалось закодировать JPEG")?;
+    Ok((buf.len() < bytes.len()).then_some(buf))
+}
+
+/// Байты изображения для Vision-запроса. При `vision_max_edge: 0` или ошибке
+/// обработки отправляется оригинал. Декодирование, уменьшение и кодирование
+/// идут в отдельном потоке, не занимая рантайм.
+pub async fn prepare(config: &Config, bytes: &[u8]) -> Vec<u8> {
+    if config.vision_max_edge == 0 {
+        return bytes.to_vec();
+    }
+    let (max_edge, quality) = (config.vision_max_edge, config.vision_jpeg_quality);
+    let original = bytes.to_vec();
+    let result = tokio::task::spawn_blocking(move || downscale(&original, max_edge, quality))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|r| r);
+    match result {
+        Ok(Some(small)) => {
+            log(
+                "ai",
+                "vision",
+                Level::Info,
+                "Изображение уменьшено для запроса",
+            )
+            .data("before", bytes.len().to_string())
+            .data("after", small.len().to_string())
+            .data("saved", (bytes.len() - small.len()).to_string())
+            .print();
+            small
+        }
+        Ok(None) => bytes.to_vec(),
+        Err(err) => {
+            log(
+                "ai",
+                "vision",
+                Level::Warn,
+                "Не удалось уменьшить изображение, отправляем оригинал",
+            )
+            .data("error", err.to_string())
+            .print();
+            bytes.to_vec()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn downscales_long_edge_to_jpeg() {
+        let img = RgbImage::from_fn(400, 200, |x, y| Rgb([(x % 256) as u8, (y % 256) as u8, 90]));
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgb8(img)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let out = downscale(&png, 100, 85)
+            .unwrap()
+            .expect("ожидается уменьшенная копия");
+        assert_eq!(image::guess_format(&out).unwrap(), image::ImageFormat::Jpeg);
+        let decoded = image::load_from_memory(&out).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (100, 50));
+    }
+}